@@ -0,0 +1,149 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#ifndef STIMMGABEL_H
+#define STIMMGABEL_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Raw, binary DER
+ */
+#define STIMMGABEL_FORMAT_DER 0
+
+/**
+ * Base64 encoded DER
+ */
+#define STIMMGABEL_FORMAT_DER_BASE64 1
+
+/**
+ * Hex encoded DER
+ */
+#define STIMMGABEL_FORMAT_DER_HEX 2
+
+#define STIMMGABEL_FORMAT_PEM 3
+
+#define STIMMGABEL_TARGET_ACTOR 0
+
+#define STIMMGABEL_TARGET_HOMESERVER 1
+
+#define STIMMGABEL_ALGORITHM_ED25519 0
+
+#define STIMMGABEL_ALGORITHM_ED448 1
+
+#define STIMMGABEL_ALGORITHM_ECDSA_P256 2
+
+#define STIMMGABEL_ALGORITHM_ECDSA_P384 3
+
+#define STIMMGABEL_ALGORITHM_ML_DSA_44 4
+
+#define STIMMGABEL_ALGORITHM_ML_DSA_65 5
+
+/**
+ * Returned by the verification functions if stimmgabel panicked during the verification, which
+ * is a bug in stimmgabel. No report is handed out in this case.
+ */
+#define STIMMGABEL_PANIC -1
+
+typedef struct StimmgabelKeyRing StimmgabelKeyRing;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Load the keys to verify with from the given files, which are read like the arguments of
+ * `--actor-key` and `--issuer-key`. Either path may be NULL, in which case the built-in key is
+ * used. Returns NULL if a file cannot be loaded or stimmgabel panicked. The returned key ring has
+ * to be freed with [stimmgabel_keyring_free].
+ *
+ * # Safety
+ *
+ * `actor_key` and `issuer_key` have to be NULL or point to NUL-terminated strings.
+ */
+StimmgabelKeyRing *stimmgabel_keyring_load(const char *actor_key, const char *issuer_key);
+
+/**
+ * Free a key ring returned by [stimmgabel_keyring_load].
+ *
+ * # Safety
+ *
+ * `keys` has to be NULL or a key ring returned by [stimmgabel_keyring_load], which has not been
+ * freed yet.
+ */
+void stimmgabel_keyring_free(StimmgabelKeyRing *keys);
+
+/**
+ * Verify an encoded ID-Cert like `stimmgabel verify id-cert` does. `at` is the time at which the
+ * certificate has to be valid in seconds since the UNIX epoch, or 0 for now. `keys` may be NULL to
+ * verify with the built-in keys. Returns the exit code of the verification. If `report` is not
+ * NULL, it is set to the JSON encoded verification report, which has to be freed with
+ * [stimmgabel_report_free]. If stimmgabel panicked, [STIMMGABEL_PANIC] is returned and `report`
+ * is set to NULL.
+ *
+ * # Safety
+ *
+ * `value` has to point to `value_len` readable bytes, `keys` has to be NULL or a valid key ring and
+ * `report` has to be NULL or point to writable memory.
+ */
+int32_t stimmgabel_verify_id_cert(const uint8_t *value,
+                                  size_t value_len,
+                                  uint32_t encoding,
+                                  uint32_t target,
+                                  uint64_t at,
+                                  const StimmgabelKeyRing *keys,
+                                  char **report);
+
+/**
+ * Verify an encoded ID-CSR like `stimmgabel verify id-csr` does. See [stimmgabel_verify_id_cert]
+ * for the meaning of the arguments and the return value.
+ *
+ * # Safety
+ *
+ * `value` has to point to `value_len` readable bytes, `keys` has to be NULL or a valid key ring and
+ * `report` has to be NULL or point to writable memory.
+ */
+int32_t stimmgabel_verify_id_csr(const uint8_t *value,
+                                 size_t value_len,
+                                 uint32_t encoding,
+                                 uint32_t target,
+                                 const StimmgabelKeyRing *keys,
+                                 char **report);
+
+/**
+ * Verify a JSON encoded message like `stimmgabel verify message` does. The message has to be signed
+ * with `algorithm`. See [stimmgabel_verify_id_cert] for the meaning of `report` and the return
+ * value.
+ *
+ * # Safety
+ *
+ * `value` has to point to `value_len` readable bytes and `report` has to be NULL or point to
+ * writable memory.
+ */
+int32_t stimmgabel_verify_message(const uint8_t *value,
+                                  size_t value_len,
+                                  uint32_t algorithm,
+                                  char **report);
+
+/**
+ * Free a report returned by one of the verification functions.
+ *
+ * # Safety
+ *
+ * `report` has to be NULL or a report returned by one of the verification functions, which has not
+ * been freed yet.
+ */
+void stimmgabel_report_free(char *report);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* STIMMGABEL_H */
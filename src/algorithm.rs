@@ -0,0 +1,614 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use ed25519_dalek::pkcs8::{DecodePrivateKey, PrivateKeyInfo};
+use polyproto::certs::PublicKeyInfo;
+use polyproto::der::asn1::BitString;
+use polyproto::der::Decode;
+use polyproto::errors::base::InvalidInput;
+use polyproto::errors::composite::ConversionError;
+use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::signature::Signature;
+use polyproto::spki::DecodePublicKey;
+
+use polyproto::spki::AlgorithmIdentifierOwned;
+
+use crate::builtin_keys::{
+    ECDSA_P256_PRIVATE_ACTOR, ECDSA_P256_PRIVATE_ACTOR_KEY, ECDSA_P256_PRIVATE_HOMESERVER,
+    ECDSA_P256_PRIVATE_HOMESERVER_KEY, ECDSA_P256_PUBLIC_ACTOR, ECDSA_P256_PUBLIC_ACTOR_KEY,
+    ECDSA_P256_PUBLIC_HOMESERVER, ECDSA_P256_PUBLIC_HOMESERVER_KEY, ECDSA_P384_PRIVATE_ACTOR,
+    ECDSA_P384_PRIVATE_ACTOR_KEY, ECDSA_P384_PRIVATE_HOMESERVER, ECDSA_P384_PRIVATE_HOMESERVER_KEY,
+    ECDSA_P384_PUBLIC_ACTOR, ECDSA_P384_PUBLIC_ACTOR_KEY, ECDSA_P384_PUBLIC_HOMESERVER,
+    ECDSA_P384_PUBLIC_HOMESERVER_KEY, ED25519_PRIVATE_ACTOR, ED25519_PRIVATE_ACTOR_KEY,
+    ED25519_PRIVATE_HOMESERVER, ED25519_PRIVATE_HOMESERVER_KEY, ED25519_PUBLIC_ACTOR,
+    ED25519_PUBLIC_ACTOR_KEY, ED25519_PUBLIC_HOMESERVER, ED25519_PUBLIC_HOMESERVER_KEY,
+    ED448_PRIVATE_ACTOR, ED448_PRIVATE_ACTOR_KEY, ED448_PRIVATE_HOMESERVER,
+    ED448_PRIVATE_HOMESERVER_KEY, ED448_PUBLIC_ACTOR, ED448_PUBLIC_ACTOR_KEY,
+    ED448_PUBLIC_HOMESERVER, ED448_PUBLIC_HOMESERVER_KEY, ML_DSA_44_PRIVATE_ACTOR,
+    ML_DSA_44_PRIVATE_HOMESERVER, ML_DSA_44_PUBLIC_ACTOR, ML_DSA_44_PUBLIC_HOMESERVER,
+    ML_DSA_65_PRIVATE_ACTOR, ML_DSA_65_PRIVATE_HOMESERVER, ML_DSA_65_PUBLIC_ACTOR,
+    ML_DSA_65_PUBLIC_HOMESERVER,
+};
+use crate::options::{KeyChoice, SignatureAlgorithm, Target};
+use crate::polyproto::keys::{
+    PrivateKeyEcdsaP256, PrivateKeyEcdsaP384, PrivateKeyEd25519, PrivateKeyEd448,
+    PrivateKeyMlDsa44, PrivateKeyMlDsa65, PublicKeyEcdsaP256, PublicKeyEcdsaP384, PublicKeyEd25519,
+    PublicKeyEd448, PublicKeyMlDsa44, PublicKeyMlDsa65,
+};
+use crate::polyproto::signature::{
+    SignatureEcdsaP256, SignatureEcdsaP384, SignatureEd25519, SignatureEd448, SignatureMlDsa44,
+    SignatureMlDsa65,
+};
+
+/// A signature algorithm stimmgabel implements polyproto for. Ties together the implementations of
+/// polyproto's [Signature], [PublicKey] and [PrivateKey] traits for the algorithm and provides
+/// access to the algorithm's built-in test keys.
+pub trait Algorithm {
+    type Signature: Signature;
+    type PublicKey: PublicKey<Self::Signature>;
+    type PrivateKey: PrivateKey<Self::Signature, PublicKey = Self::PublicKey>;
+
+    /// The built-in test key of the given [KeyChoice], PEM encoded
+    fn builtin_key_pem(choice: KeyChoice) -> &'static str;
+    /// The built-in public test key of the given [Target]
+    fn builtin_public_key(target: Target) -> Self::PublicKey;
+    /// The built-in private test key of the given [Target]
+    fn builtin_private_key(target: Target) -> Self::PrivateKey;
+    /// Parse a DER encoded SPKI public key
+    fn public_key_from_der(der: &[u8]) -> Result<Self::PublicKey, String>;
+    /// Parse a DER encoded PKCS#8 private key
+    fn private_key_from_der(der: &[u8]) -> Result<Self::PrivateKey, String>;
+    /// Parse a raw signature, as found in messages. Unlike [Signature::from_bytes], this returns an
+    /// error instead of panicking if the bytes are not a valid signature.
+    fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, ConversionError>;
+
+    /// The algorithm identifier of public keys of this algorithm. For most algorithms, this is the
+    /// same as the identifier of the signature algorithm.
+    fn public_key_algorithm_identifier() -> AlgorithmIdentifierOwned {
+        Self::Signature::algorithm_identifier()
+    }
+
+    /// Parse a raw public key, as found in messages
+    fn public_key_from_bytes(bytes: &[u8]) -> Result<Self::PublicKey, ConversionError> {
+        Self::PublicKey::try_from_public_key_info(PublicKeyInfo {
+            algorithm: Self::public_key_algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(bytes)
+                .map_err(ConversionError::DerError)?,
+        })
+    }
+}
+
+/// Run `$body` with the type `$algorithm` being the [Algorithm] implementation selected by the
+/// [crate::options::SignatureAlgorithm] `$choice`
+#[macro_export]
+macro_rules! with_algorithm {
+    ($choice:expr, $algorithm:ident => $body:expr) => {
+        match $choice {
+            $crate::options::SignatureAlgorithm::Ed25519 => {
+                type $algorithm = $crate::algorithm::Ed25519;
+                $body
+            }
+            $crate::options::SignatureAlgorithm::Ed448 => {
+                type $algorithm = $crate::algorithm::Ed448;
+                $body
+            }
+            $crate::options::SignatureAlgorithm::EcdsaP256 => {
+                type $algorithm = $crate::algorithm::EcdsaP256;
+                $body
+            }
+            $crate::options::SignatureAlgorithm::EcdsaP384 => {
+                type $algorithm = $crate::algorithm::EcdsaP384;
+                $body
+            }
+            $crate::options::SignatureAlgorithm::MlDsa44 => {
+                type $algorithm = $crate::algorithm::MlDsa44;
+                $body
+            }
+            $crate::options::SignatureAlgorithm::MlDsa65 => {
+                type $algorithm = $crate::algorithm::MlDsa65;
+                $body
+            }
+        }
+    };
+}
+pub use crate::with_algorithm;
+
+impl SignatureAlgorithm {
+    /// The [SignatureAlgorithm] identified by the signature algorithm identifier of a certificate or
+    /// CSR, if stimmgabel implements it
+    pub fn from_algorithm_identifier(
+        algorithm_identifier: &AlgorithmIdentifierOwned,
+    ) -> Option<Self> {
+        <SignatureAlgorithm as clap::ValueEnum>::value_variants()
+            .iter()
+            .copied()
+            .find(|algorithm| {
+                with_algorithm!(
+                    algorithm,
+                    A => <<A as Algorithm>::Signature as Signature>::algorithm_identifier().oid
+                ) == algorithm_identifier.oid
+            })
+    }
+
+    /// The algorithm identifier public keys of this algorithm are expected to have
+    pub fn public_key_algorithm_identifier(&self) -> AlgorithmIdentifierOwned {
+        with_algorithm!(self, A => A::public_key_algorithm_identifier())
+    }
+}
+
+/// The result of reading the algorithm identifiers of a DER encoded ID-Cert or ID-CSR
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DetectedAlgorithm {
+    /// The value is signed with an algorithm stimmgabel implements, and its subject public key
+    /// belongs to the same algorithm
+    Supported(SignatureAlgorithm),
+    /// The value names an algorithm stimmgabel does not implement. Contains a description of the
+    /// unsupported algorithm
+    Unsupported(String),
+    /// The algorithm identifiers could not be read, because the value is not a well-formed
+    /// certificate or CSR. Reading the value will fail during verification
+    Unreadable,
+}
+
+/// Read the signature and subject public key algorithm identifiers of a DER encoded ID-Cert or
+/// ID-CSR and determine which [SignatureAlgorithm] the value has to be verified with. Values whose
+/// subject public key belongs to a different algorithm than their signature are not supported, since
+/// polyproto's certificate types use one algorithm for both.
+pub fn detect_algorithm(der: &[u8]) -> DetectedAlgorithm {
+    let (signature_algorithm, public_key_algorithm) = match x509_cert::Certificate::from_der(der) {
+        Ok(certificate) => (
+            certificate.signature_algorithm,
+            certificate
+                .tbs_certificate
+                .subject_public_key_info
+                .algorithm,
+        ),
+        Err(_) => match x509_cert::request::CertReq::from_der(der) {
+            Ok(request) => (request.algorithm, request.info.public_key.algorithm),
+            Err(_) => return DetectedAlgorithm::Unreadable,
+        },
+    };
+    let Some(algorithm) = SignatureAlgorithm::from_algorithm_identifier(&signature_algorithm)
+    else {
+        return DetectedAlgorithm::Unsupported(format!(
+            "The signature algorithm {} is not supported",
+            signature_algorithm.oid
+        ));
+    };
+    if public_key_algorithm != algorithm.public_key_algorithm_identifier() {
+        return DetectedAlgorithm::Unsupported(format!(
+            "The subject public key algorithm {} is not supported for {} signatures",
+            public_key_algorithm.oid, algorithm
+        ));
+    }
+    DetectedAlgorithm::Supported(algorithm)
+}
+
+/// Check that the signature of a DER encoded ID-Cert or ID-CSR is a well-formed signature of the
+/// [Algorithm] `A`. polyproto creates signatures from their bytes without checking them, which would
+/// panic for algorithms with fixed-length signatures, so this has to be done before parsing the
+/// value. Values which cannot be read are not checked, as parsing them fails anyway.
+pub fn check_signature<A: Algorithm>(der: &[u8]) -> Result<(), ConversionError> {
+    let signature = match x509_cert::Certificate::from_der(der) {
+        Ok(certificate) => certificate.signature,
+        Err(_) => match x509_cert::request::CertReq::from_der(der) {
+            Ok(request) => request.signature,
+            Err(_) => return Ok(()),
+        },
+    };
+    A::signature_from_bytes(signature.raw_bytes()).map(|_| ())
+}
+
+/// Parse a DER encoded PKCS#8 ML-DSA private key of the given algorithm. Only keys consisting of
+/// the 32 byte seed the key pair is derived from are supported, as the expanded private key does
+/// not allow deriving the public key.
+fn ml_dsa_seed_from_der(
+    der: &[u8],
+    algorithm: AlgorithmIdentifierOwned,
+) -> Result<[u8; 32], String> {
+    let private_key_info = PrivateKeyInfo::try_from(der).map_err(|e| e.to_string())?;
+    if private_key_info.algorithm.oid != algorithm.oid {
+        return Err(format!(
+            "Expected a key of algorithm {}, got {}",
+            algorithm.oid, private_key_info.algorithm.oid
+        ));
+    }
+    // The seed is encoded as `[0] IMPLICIT OCTET STRING (SIZE (32))`
+    match private_key_info.private_key {
+        [0x80, 0x20, seed @ ..] => seed
+            .try_into()
+            .map_err(|_| "The private key seed is not 32 bytes long".to_string()),
+        _ => Err(
+            "Only ML-DSA private keys in the seed format are supported. OpenSSL exports them with \
+             -provparam ml-dsa.output_formats=seed-only"
+                .to_string(),
+        ),
+    }
+}
+
+/// The error returned when a signature or key does not have the length its algorithm requires
+fn wrong_length(what: &str, expected: usize, actual: usize) -> ConversionError {
+    ConversionError::InvalidInput(InvalidInput::Malformed(format!(
+        "Expected {} of {} bytes, got {} bytes",
+        what, expected, actual
+    )))
+}
+
+pub struct Ed25519;
+
+impl Algorithm for Ed25519 {
+    type Signature = SignatureEd25519;
+    type PublicKey = PublicKeyEd25519;
+    type PrivateKey = PrivateKeyEd25519;
+
+    fn builtin_key_pem(choice: KeyChoice) -> &'static str {
+        match choice {
+            KeyChoice::ActorPrivate => ED25519_PRIVATE_ACTOR.trim_end(),
+            KeyChoice::ActorPublic => ED25519_PUBLIC_ACTOR.trim_end(),
+            KeyChoice::HomeserverPrivate => ED25519_PRIVATE_HOMESERVER.trim_end(),
+            KeyChoice::HomeserverPublic => ED25519_PUBLIC_HOMESERVER.trim_end(),
+        }
+    }
+
+    fn builtin_public_key(target: Target) -> Self::PublicKey {
+        let key = match target {
+            Target::Actor => *ED25519_PUBLIC_ACTOR_KEY,
+            Target::Homeserver => *ED25519_PUBLIC_HOMESERVER_KEY,
+        };
+        PublicKeyEd25519 { key }
+    }
+
+    fn builtin_private_key(target: Target) -> Self::PrivateKey {
+        let key = match target {
+            Target::Actor => ED25519_PRIVATE_ACTOR_KEY.clone(),
+            Target::Homeserver => ED25519_PRIVATE_HOMESERVER_KEY.clone(),
+        };
+        PrivateKeyEd25519::from(key)
+    }
+
+    fn public_key_from_der(der: &[u8]) -> Result<Self::PublicKey, String> {
+        ed25519_dalek::VerifyingKey::from_public_key_der(der)
+            .map(|key| PublicKeyEd25519 { key })
+            .map_err(|e| e.to_string())
+    }
+
+    fn private_key_from_der(der: &[u8]) -> Result<Self::PrivateKey, String> {
+        ed25519_dalek::SigningKey::from_pkcs8_der(der)
+            .map(PrivateKeyEd25519::from)
+            .map_err(|e| e.to_string())
+    }
+
+    fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, ConversionError> {
+        if bytes.len() != ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(wrong_length(
+                "a signature",
+                ed25519_dalek::SIGNATURE_LENGTH,
+                bytes.len(),
+            ));
+        }
+        Ok(SignatureEd25519::from_bytes(bytes))
+    }
+}
+
+pub struct Ed448;
+
+impl Algorithm for Ed448 {
+    type Signature = SignatureEd448;
+    type PublicKey = PublicKeyEd448;
+    type PrivateKey = PrivateKeyEd448;
+
+    fn builtin_key_pem(choice: KeyChoice) -> &'static str {
+        match choice {
+            KeyChoice::ActorPrivate => ED448_PRIVATE_ACTOR.trim_end(),
+            KeyChoice::ActorPublic => ED448_PUBLIC_ACTOR.trim_end(),
+            KeyChoice::HomeserverPrivate => ED448_PRIVATE_HOMESERVER.trim_end(),
+            KeyChoice::HomeserverPublic => ED448_PUBLIC_HOMESERVER.trim_end(),
+        }
+    }
+
+    fn builtin_public_key(target: Target) -> Self::PublicKey {
+        let key = match target {
+            Target::Actor => *ED448_PUBLIC_ACTOR_KEY,
+            Target::Homeserver => *ED448_PUBLIC_HOMESERVER_KEY,
+        };
+        PublicKeyEd448 { key }
+    }
+
+    fn builtin_private_key(target: Target) -> Self::PrivateKey {
+        let key = match target {
+            Target::Actor => ED448_PRIVATE_ACTOR_KEY.clone(),
+            Target::Homeserver => ED448_PRIVATE_HOMESERVER_KEY.clone(),
+        };
+        PrivateKeyEd448::from(key)
+    }
+
+    fn public_key_from_der(der: &[u8]) -> Result<Self::PublicKey, String> {
+        ed448_goldilocks_plus::VerifyingKey::from_public_key_der(der)
+            .map(|key| PublicKeyEd448 { key })
+            .map_err(|e| e.to_string())
+    }
+
+    fn private_key_from_der(der: &[u8]) -> Result<Self::PrivateKey, String> {
+        ed448_goldilocks_plus::SigningKey::from_pkcs8_der(der)
+            .map(PrivateKeyEd448::from)
+            .map_err(|e| e.to_string())
+    }
+
+    fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, ConversionError> {
+        match ed448_goldilocks_plus::Signature::try_from(bytes) {
+            Ok(signature) => Ok(SignatureEd448 { signature }),
+            Err(_) if bytes.len() != 114 => Err(wrong_length("a signature", 114, bytes.len())),
+            Err(e) => Err(ConversionError::InvalidInput(InvalidInput::Malformed(
+                e.to_string(),
+            ))),
+        }
+    }
+}
+
+pub struct EcdsaP256;
+
+impl Algorithm for EcdsaP256 {
+    type Signature = SignatureEcdsaP256;
+    type PublicKey = PublicKeyEcdsaP256;
+    type PrivateKey = PrivateKeyEcdsaP256;
+
+    fn builtin_key_pem(choice: KeyChoice) -> &'static str {
+        match choice {
+            KeyChoice::ActorPrivate => ECDSA_P256_PRIVATE_ACTOR.trim_end(),
+            KeyChoice::ActorPublic => ECDSA_P256_PUBLIC_ACTOR.trim_end(),
+            KeyChoice::HomeserverPrivate => ECDSA_P256_PRIVATE_HOMESERVER.trim_end(),
+            KeyChoice::HomeserverPublic => ECDSA_P256_PUBLIC_HOMESERVER.trim_end(),
+        }
+    }
+
+    fn builtin_public_key(target: Target) -> Self::PublicKey {
+        let key = match target {
+            Target::Actor => ECDSA_P256_PUBLIC_ACTOR_KEY.clone(),
+            Target::Homeserver => ECDSA_P256_PUBLIC_HOMESERVER_KEY.clone(),
+        };
+        PublicKeyEcdsaP256 { key }
+    }
+
+    fn builtin_private_key(target: Target) -> Self::PrivateKey {
+        let key = match target {
+            Target::Actor => ECDSA_P256_PRIVATE_ACTOR_KEY.clone(),
+            Target::Homeserver => ECDSA_P256_PRIVATE_HOMESERVER_KEY.clone(),
+        };
+        PrivateKeyEcdsaP256::from(key)
+    }
+
+    fn public_key_from_der(der: &[u8]) -> Result<Self::PublicKey, String> {
+        p256::ecdsa::VerifyingKey::from_public_key_der(der)
+            .map(|key| PublicKeyEcdsaP256 { key })
+            .map_err(|e| e.to_string())
+    }
+
+    fn private_key_from_der(der: &[u8]) -> Result<Self::PrivateKey, String> {
+        p256::ecdsa::SigningKey::from_pkcs8_der(der)
+            .map(PrivateKeyEcdsaP256::from)
+            .map_err(|e| e.to_string())
+    }
+
+    /// ECDSA signatures are DER encoded, both in certificates and in messages
+    fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, ConversionError> {
+        p256::ecdsa::Signature::from_der(bytes).map_err(|e| {
+            ConversionError::InvalidInput(InvalidInput::Malformed(format!(
+                "Expected a DER encoded ECDSA signature: {}",
+                e
+            )))
+        })?;
+        Ok(SignatureEcdsaP256::from_bytes(bytes))
+    }
+
+    fn public_key_algorithm_identifier() -> AlgorithmIdentifierOwned {
+        PublicKeyEcdsaP256::algorithm_identifier()
+    }
+}
+
+pub struct EcdsaP384;
+
+impl Algorithm for EcdsaP384 {
+    type Signature = SignatureEcdsaP384;
+    type PublicKey = PublicKeyEcdsaP384;
+    type PrivateKey = PrivateKeyEcdsaP384;
+
+    fn builtin_key_pem(choice: KeyChoice) -> &'static str {
+        match choice {
+            KeyChoice::ActorPrivate => ECDSA_P384_PRIVATE_ACTOR.trim_end(),
+            KeyChoice::ActorPublic => ECDSA_P384_PUBLIC_ACTOR.trim_end(),
+            KeyChoice::HomeserverPrivate => ECDSA_P384_PRIVATE_HOMESERVER.trim_end(),
+            KeyChoice::HomeserverPublic => ECDSA_P384_PUBLIC_HOMESERVER.trim_end(),
+        }
+    }
+
+    fn builtin_public_key(target: Target) -> Self::PublicKey {
+        let key = match target {
+            Target::Actor => ECDSA_P384_PUBLIC_ACTOR_KEY.clone(),
+            Target::Homeserver => ECDSA_P384_PUBLIC_HOMESERVER_KEY.clone(),
+        };
+        PublicKeyEcdsaP384 { key }
+    }
+
+    fn builtin_private_key(target: Target) -> Self::PrivateKey {
+        let key = match target {
+            Target::Actor => ECDSA_P384_PRIVATE_ACTOR_KEY.clone(),
+            Target::Homeserver => ECDSA_P384_PRIVATE_HOMESERVER_KEY.clone(),
+        };
+        PrivateKeyEcdsaP384::from(key)
+    }
+
+    fn public_key_from_der(der: &[u8]) -> Result<Self::PublicKey, String> {
+        p384::ecdsa::VerifyingKey::from_public_key_der(der)
+            .map(|key| PublicKeyEcdsaP384 { key })
+            .map_err(|e| e.to_string())
+    }
+
+    fn private_key_from_der(der: &[u8]) -> Result<Self::PrivateKey, String> {
+        p384::ecdsa::SigningKey::from_pkcs8_der(der)
+            .map(PrivateKeyEcdsaP384::from)
+            .map_err(|e| e.to_string())
+    }
+
+    /// ECDSA signatures are DER encoded, both in certificates and in messages
+    fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, ConversionError> {
+        p384::ecdsa::Signature::from_der(bytes).map_err(|e| {
+            ConversionError::InvalidInput(InvalidInput::Malformed(format!(
+                "Expected a DER encoded ECDSA signature: {}",
+                e
+            )))
+        })?;
+        Ok(SignatureEcdsaP384::from_bytes(bytes))
+    }
+
+    fn public_key_algorithm_identifier() -> AlgorithmIdentifierOwned {
+        PublicKeyEcdsaP384::algorithm_identifier()
+    }
+}
+
+pub struct MlDsa44;
+
+impl Algorithm for MlDsa44 {
+    type Signature = SignatureMlDsa44;
+    type PublicKey = PublicKeyMlDsa44;
+    type PrivateKey = PrivateKeyMlDsa44;
+
+    fn builtin_key_pem(choice: KeyChoice) -> &'static str {
+        match choice {
+            KeyChoice::ActorPrivate => ML_DSA_44_PRIVATE_ACTOR.trim_end(),
+            KeyChoice::ActorPublic => ML_DSA_44_PUBLIC_ACTOR.trim_end(),
+            KeyChoice::HomeserverPrivate => ML_DSA_44_PRIVATE_HOMESERVER.trim_end(),
+            KeyChoice::HomeserverPublic => ML_DSA_44_PUBLIC_HOMESERVER.trim_end(),
+        }
+    }
+
+    fn builtin_public_key(target: Target) -> Self::PublicKey {
+        let pem = match target {
+            Target::Actor => ML_DSA_44_PUBLIC_ACTOR.trim_end(),
+            Target::Homeserver => ML_DSA_44_PUBLIC_HOMESERVER.trim_end(),
+        };
+        // Unwraps are ok because the built-in keys are known to be valid
+        let (_, der) = polyproto::der::pem::decode_vec(pem.as_bytes()).unwrap();
+        Self::public_key_from_der(&der).unwrap()
+    }
+
+    fn builtin_private_key(target: Target) -> Self::PrivateKey {
+        let pem = match target {
+            Target::Actor => ML_DSA_44_PRIVATE_ACTOR.trim_end(),
+            Target::Homeserver => ML_DSA_44_PRIVATE_HOMESERVER.trim_end(),
+        };
+        // Unwraps are ok because the built-in keys are known to be valid
+        let (_, der) = polyproto::der::pem::decode_vec(pem.as_bytes()).unwrap();
+        Self::private_key_from_der(&der).unwrap()
+    }
+
+    fn public_key_from_der(der: &[u8]) -> Result<Self::PublicKey, String> {
+        let public_key_info =
+            polyproto::spki::SubjectPublicKeyInfoOwned::try_from(der).map_err(|e| e.to_string())?;
+        PublicKeyMlDsa44::try_from_public_key_info(PublicKeyInfo {
+            algorithm: public_key_info.algorithm,
+            public_key_bitstring: public_key_info.subject_public_key,
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn private_key_from_der(der: &[u8]) -> Result<Self::PrivateKey, String> {
+        ml_dsa_seed_from_der(der, SignatureMlDsa44::algorithm_identifier())
+            .map(PrivateKeyMlDsa44::from_seed)
+    }
+
+    fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, ConversionError> {
+        if bytes.len() != fips204::ml_dsa_44::SIG_LEN {
+            return Err(wrong_length(
+                "a signature",
+                fips204::ml_dsa_44::SIG_LEN,
+                bytes.len(),
+            ));
+        }
+        Ok(SignatureMlDsa44::from_bytes(bytes))
+    }
+}
+
+pub struct MlDsa65;
+
+impl Algorithm for MlDsa65 {
+    type Signature = SignatureMlDsa65;
+    type PublicKey = PublicKeyMlDsa65;
+    type PrivateKey = PrivateKeyMlDsa65;
+
+    fn builtin_key_pem(choice: KeyChoice) -> &'static str {
+        match choice {
+            KeyChoice::ActorPrivate => ML_DSA_65_PRIVATE_ACTOR.trim_end(),
+            KeyChoice::ActorPublic => ML_DSA_65_PUBLIC_ACTOR.trim_end(),
+            KeyChoice::HomeserverPrivate => ML_DSA_65_PRIVATE_HOMESERVER.trim_end(),
+            KeyChoice::HomeserverPublic => ML_DSA_65_PUBLIC_HOMESERVER.trim_end(),
+        }
+    }
+
+    fn builtin_public_key(target: Target) -> Self::PublicKey {
+        let pem = match target {
+            Target::Actor => ML_DSA_65_PUBLIC_ACTOR.trim_end(),
+            Target::Homeserver => ML_DSA_65_PUBLIC_HOMESERVER.trim_end(),
+        };
+        // Unwraps are ok because the built-in keys are known to be valid
+        let (_, der) = polyproto::der::pem::decode_vec(pem.as_bytes()).unwrap();
+        Self::public_key_from_der(&der).unwrap()
+    }
+
+    fn builtin_private_key(target: Target) -> Self::PrivateKey {
+        let pem = match target {
+            Target::Actor => ML_DSA_65_PRIVATE_ACTOR.trim_end(),
+            Target::Homeserver => ML_DSA_65_PRIVATE_HOMESERVER.trim_end(),
+        };
+        // Unwraps are ok because the built-in keys are known to be valid
+        let (_, der) = polyproto::der::pem::decode_vec(pem.as_bytes()).unwrap();
+        Self::private_key_from_der(&der).unwrap()
+    }
+
+    fn public_key_from_der(der: &[u8]) -> Result<Self::PublicKey, String> {
+        let public_key_info =
+            polyproto::spki::SubjectPublicKeyInfoOwned::try_from(der).map_err(|e| e.to_string())?;
+        PublicKeyMlDsa65::try_from_public_key_info(PublicKeyInfo {
+            algorithm: public_key_info.algorithm,
+            public_key_bitstring: public_key_info.subject_public_key,
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn private_key_from_der(der: &[u8]) -> Result<Self::PrivateKey, String> {
+        ml_dsa_seed_from_der(der, SignatureMlDsa65::algorithm_identifier())
+            .map(PrivateKeyMlDsa65::from_seed)
+    }
+
+    fn signature_from_bytes(bytes: &[u8]) -> Result<Self::Signature, ConversionError> {
+        if bytes.len() != fips204::ml_dsa_65::SIG_LEN {
+            return Err(wrong_length(
+                "a signature",
+                fips204::ml_dsa_65::SIG_LEN,
+                bytes.len(),
+            ));
+        }
+        Ok(SignatureMlDsa65::from_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Target;
+
+    use super::{Algorithm, MlDsa44, MlDsa65};
+
+    #[test]
+    fn builtin_ml_dsa_public_keys_belong_to_the_private_keys() {
+        for target in [Target::Actor, Target::Homeserver] {
+            assert_eq!(
+                MlDsa44::builtin_public_key(target),
+                MlDsa44::builtin_private_key(target).public_key
+            );
+            assert_eq!(
+                MlDsa65::builtin_public_key(target),
+                MlDsa65::builtin_private_key(target).public_key
+            );
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! The built-in test keys, which stimmgabel signs and verifies with unless told otherwise
+
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use lazy_static::lazy_static;
+use polyproto::spki::DecodePublicKey;
+
+pub(crate) static ED25519_PRIVATE_ACTOR: &str = include_str!("../keys/private_actor.pem");
+pub(crate) static ED25519_PUBLIC_ACTOR: &str = include_str!("../keys/public_actor.pem");
+pub(crate) static ED25519_PRIVATE_HOMESERVER: &str = include_str!("../keys/private_homeserver.pem");
+pub(crate) static ED25519_PUBLIC_HOMESERVER: &str = include_str!("../keys/public_homeserver.pem");
+pub(crate) static ED448_PRIVATE_ACTOR: &str = include_str!("../keys/private_actor_ed448.pem");
+pub(crate) static ED448_PUBLIC_ACTOR: &str = include_str!("../keys/public_actor_ed448.pem");
+pub(crate) static ED448_PRIVATE_HOMESERVER: &str =
+    include_str!("../keys/private_homeserver_ed448.pem");
+pub(crate) static ED448_PUBLIC_HOMESERVER: &str =
+    include_str!("../keys/public_homeserver_ed448.pem");
+pub(crate) static ECDSA_P256_PRIVATE_ACTOR: &str = include_str!("../keys/private_actor_p256.pem");
+pub(crate) static ECDSA_P256_PUBLIC_ACTOR: &str = include_str!("../keys/public_actor_p256.pem");
+pub(crate) static ECDSA_P256_PRIVATE_HOMESERVER: &str =
+    include_str!("../keys/private_homeserver_p256.pem");
+pub(crate) static ECDSA_P256_PUBLIC_HOMESERVER: &str =
+    include_str!("../keys/public_homeserver_p256.pem");
+pub(crate) static ECDSA_P384_PRIVATE_ACTOR: &str = include_str!("../keys/private_actor_p384.pem");
+pub(crate) static ECDSA_P384_PUBLIC_ACTOR: &str = include_str!("../keys/public_actor_p384.pem");
+pub(crate) static ECDSA_P384_PRIVATE_HOMESERVER: &str =
+    include_str!("../keys/private_homeserver_p384.pem");
+pub(crate) static ECDSA_P384_PUBLIC_HOMESERVER: &str =
+    include_str!("../keys/public_homeserver_p384.pem");
+pub(crate) static ML_DSA_44_PRIVATE_ACTOR: &str =
+    include_str!("../keys/private_actor_ml_dsa_44.pem");
+pub(crate) static ML_DSA_44_PUBLIC_ACTOR: &str = include_str!("../keys/public_actor_ml_dsa_44.pem");
+pub(crate) static ML_DSA_44_PRIVATE_HOMESERVER: &str =
+    include_str!("../keys/private_homeserver_ml_dsa_44.pem");
+pub(crate) static ML_DSA_44_PUBLIC_HOMESERVER: &str =
+    include_str!("../keys/public_homeserver_ml_dsa_44.pem");
+pub(crate) static ML_DSA_65_PRIVATE_ACTOR: &str =
+    include_str!("../keys/private_actor_ml_dsa_65.pem");
+pub(crate) static ML_DSA_65_PUBLIC_ACTOR: &str = include_str!("../keys/public_actor_ml_dsa_65.pem");
+pub(crate) static ML_DSA_65_PRIVATE_HOMESERVER: &str =
+    include_str!("../keys/private_homeserver_ml_dsa_65.pem");
+pub(crate) static ML_DSA_65_PUBLIC_HOMESERVER: &str =
+    include_str!("../keys/public_homeserver_ml_dsa_65.pem");
+
+lazy_static! {
+    pub(crate) static ref ED25519_PRIVATE_ACTOR_KEY: SigningKey =
+        SigningKey::from_pkcs8_pem(ED25519_PRIVATE_ACTOR.trim_end()).unwrap();
+    pub(crate) static ref ED25519_PUBLIC_ACTOR_KEY: VerifyingKey =
+        VerifyingKey::from_public_key_pem(ED25519_PUBLIC_ACTOR.trim_end()).unwrap();
+    pub(crate) static ref ED25519_PRIVATE_HOMESERVER_KEY: SigningKey =
+        SigningKey::from_pkcs8_pem(ED25519_PRIVATE_HOMESERVER.trim_end()).unwrap();
+    pub(crate) static ref ED25519_PUBLIC_HOMESERVER_KEY: VerifyingKey =
+        VerifyingKey::from_public_key_pem(ED25519_PUBLIC_HOMESERVER.trim_end()).unwrap();
+    pub(crate) static ref ED448_PRIVATE_ACTOR_KEY: ed448_goldilocks_plus::SigningKey =
+        ed448_goldilocks_plus::SigningKey::from_pkcs8_pem(ED448_PRIVATE_ACTOR.trim_end()).unwrap();
+    pub(crate) static ref ED448_PUBLIC_ACTOR_KEY: ed448_goldilocks_plus::VerifyingKey =
+        ed448_goldilocks_plus::VerifyingKey::from_public_key_pem(ED448_PUBLIC_ACTOR.trim_end())
+            .unwrap();
+    pub(crate) static ref ED448_PRIVATE_HOMESERVER_KEY: ed448_goldilocks_plus::SigningKey =
+        ed448_goldilocks_plus::SigningKey::from_pkcs8_pem(ED448_PRIVATE_HOMESERVER.trim_end())
+            .unwrap();
+    pub(crate) static ref ED448_PUBLIC_HOMESERVER_KEY: ed448_goldilocks_plus::VerifyingKey =
+        ed448_goldilocks_plus::VerifyingKey::from_public_key_pem(
+            ED448_PUBLIC_HOMESERVER.trim_end()
+        )
+        .unwrap();
+    pub(crate) static ref ECDSA_P256_PRIVATE_ACTOR_KEY: p256::ecdsa::SigningKey =
+        p256::ecdsa::SigningKey::from_pkcs8_pem(ECDSA_P256_PRIVATE_ACTOR.trim_end()).unwrap();
+    pub(crate) static ref ECDSA_P256_PUBLIC_ACTOR_KEY: p256::ecdsa::VerifyingKey =
+        p256::ecdsa::VerifyingKey::from_public_key_pem(ECDSA_P256_PUBLIC_ACTOR.trim_end()).unwrap();
+    pub(crate) static ref ECDSA_P256_PRIVATE_HOMESERVER_KEY: p256::ecdsa::SigningKey =
+        p256::ecdsa::SigningKey::from_pkcs8_pem(ECDSA_P256_PRIVATE_HOMESERVER.trim_end()).unwrap();
+    pub(crate) static ref ECDSA_P256_PUBLIC_HOMESERVER_KEY: p256::ecdsa::VerifyingKey =
+        p256::ecdsa::VerifyingKey::from_public_key_pem(ECDSA_P256_PUBLIC_HOMESERVER.trim_end())
+            .unwrap();
+    pub(crate) static ref ECDSA_P384_PRIVATE_ACTOR_KEY: p384::ecdsa::SigningKey =
+        p384::ecdsa::SigningKey::from_pkcs8_pem(ECDSA_P384_PRIVATE_ACTOR.trim_end()).unwrap();
+    pub(crate) static ref ECDSA_P384_PUBLIC_ACTOR_KEY: p384::ecdsa::VerifyingKey =
+        p384::ecdsa::VerifyingKey::from_public_key_pem(ECDSA_P384_PUBLIC_ACTOR.trim_end()).unwrap();
+    pub(crate) static ref ECDSA_P384_PRIVATE_HOMESERVER_KEY: p384::ecdsa::SigningKey =
+        p384::ecdsa::SigningKey::from_pkcs8_pem(ECDSA_P384_PRIVATE_HOMESERVER.trim_end()).unwrap();
+    pub(crate) static ref ECDSA_P384_PUBLIC_HOMESERVER_KEY: p384::ecdsa::VerifyingKey =
+        p384::ecdsa::VerifyingKey::from_public_key_pem(ECDSA_P384_PUBLIC_HOMESERVER.trim_end())
+            .unwrap();
+}
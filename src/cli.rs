@@ -3,9 +3,14 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::fmt::Display;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 use clap::builder::PossibleValue;
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+pub(crate) use stimmgabel::options::{Capability, Format, KeyChoice, SignatureAlgorithm, Target};
 
 #[derive(Debug, Parser)]
 #[command(name = "stimmgabel")]
@@ -15,137 +20,447 @@ use clap::{Parser, Subcommand, ValueEnum};
 pub(crate) struct CliArguments {
     #[command(subcommand)]
     pub(crate) command: Commands,
+    /// A file containing the actor key to use instead of the built-in one. May be a PKCS#8
+    /// private key or an SPKI public key, PEM or DER encoded. Only private keys can be used for
+    /// signing
+    #[arg(long = "actor-key", global = true)]
+    pub(crate) actor_key: Option<PathBuf>,
+    /// A file containing the issuer (home server) key to use instead of the built-in one. May be a
+    /// PKCS#8 private key or an SPKI public key, PEM or DER encoded. Only private keys can be used
+    /// for signing
+    #[arg(long = "issuer-key", global = true)]
+    pub(crate) issuer_key: Option<PathBuf>,
+    /// The signature algorithm values are signed and verified with
+    #[arg(default_value_t = SignatureAlgorithm::Ed25519, long = "algorithm", global = true)]
+    pub(crate) algorithm: SignatureAlgorithm,
 }
 
 #[derive(Debug, Subcommand)]
 pub(crate) enum Commands {
-    /// Display the Ed25519 keys that should be used when supplying data to be verified. Keys are
-    /// printed in PEM format
+    /// Display the built-in keys of the selected algorithm that are used when supplying data to be
+    /// verified, unless other keys are given with --actor-key or --issuer-key. Keys are printed in
+    /// PEM format
     Keys { key_choice: KeyChoice },
     /// Verify the well-formedness as well as the syntactical and cryptographical correctness of a
     /// given polyproto value
     Verify {
+        /// Additionally print a machine-readable report of the verification to stdout
+        #[arg(long = "report", global = true)]
+        report: Option<ReportFormat>,
         /// The verification mode to use
         #[command(subcommand)]
         mode: StimmgabelMode,
     },
+    /// Produce signed polyproto values, which can be used as a known-good reference when testing
+    /// other implementations' verification logic
+    Sign {
+        /// The kind of value to produce
+        #[command(subcommand)]
+        mode: SignMode,
+    },
+    /// Act as a reference home server: Issue an ID-Cert from a given ID-CSR, signed with the issuer
+    /// key
+    Issue(IssueArgs),
+    /// Work with polyproto ID-CSRs
+    Csr {
+        #[command(subcommand)]
+        action: CsrAction,
+    },
+    /// Keep running and answer JSON-RPC 2.0 requests for verifying, signing and issuing values, so
+    /// that test suites do not have to start a new process for every value
+    Serve {
+        /// How requests are received and responses are sent
+        #[arg(default_value_t = Transport::Stdio, long = "transport")]
+        transport: Transport,
+    },
+    /// Act as a reference home server, serving the core routes of the polyproto HTTP API. The home
+    /// server signs with the issuer key
+    Server(ServerArgs),
+    /// Drive a home server implementation through the core routes of the polyproto HTTP API and
+    /// report for each route, whether the home server behaves as expected
+    ProbeServer(ProbeArgs),
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq, Clone)]
+pub(crate) enum CsrAction {
+    /// Create an ID-CSR, which can be used as a known-good reference when testing other
+    /// implementations' certificate issuance
+    Create {
+        /// The RDN sequence of the subject, e.g.
+        /// "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1"
+        subject: String,
+        /// Whether the ID-CSR requests the capabilities of an actor or of a home server
+        #[arg(long = "target")]
+        target: Target,
+        /// Whose private key to sign with
+        #[arg(default_value_t = Target::Actor, long = "key")]
+        key: Target,
+        #[arg(default_value_t = Format::Pem, long = "encoding")]
+        /// The format, in which the ID-CSR is printed
+        encoding: Format,
+    },
+}
+
+/// The issuer of issued certificates, if none is given by the user
+pub(crate) const DEFAULT_ISSUER: &str = "DC=polyphony,DC=chat";
+
+/// The arguments of the `issue` command
+#[derive(Debug, Args, PartialEq, Eq, Clone)]
+pub(crate) struct IssueArgs {
+    #[command(flatten)]
+    pub(crate) input: Input,
+    #[arg(default_value_t = Format::Der, long = "encoding")]
+    /// The format, in which the ID-CSR is encoded
+    pub(crate) encoding: Format,
+    #[arg(default_value_t = Format::Pem, long = "output-encoding")]
+    /// The format, in which the issued ID-Cert is printed
+    pub(crate) output_encoding: Format,
+    /// Who the ID-CSR and therefore the issued certificate is for
+    #[arg(long = "target")]
+    pub(crate) target: Target,
+    /// The serial number of the issued certificate
+    #[arg(default_value_t = 1, long = "serial")]
+    pub(crate) serial: u64,
+    /// The RDN sequence of the issuer, e.g. "DC=polyphony,DC=chat"
+    #[arg(default_value = DEFAULT_ISSUER, long = "issuer")]
+    pub(crate) issuer: String,
+    /// The RFC 3339 timestamp from which on the certificate is valid. Defaults to now
+    #[arg(long = "not-before", value_parser = parse_timestamp)]
+    pub(crate) not_before: Option<u64>,
+    /// The RFC 3339 timestamp until which the certificate is valid. Defaults to one year after
+    /// the start of the validity period
+    #[arg(long = "not-after", value_parser = parse_timestamp)]
+    pub(crate) not_after: Option<u64>,
+}
+
+/// The arguments of the `server` command
+#[derive(Debug, Args, PartialEq, Eq, Clone)]
+pub(crate) struct ServerArgs {
+    /// The address to listen on
+    #[arg(default_value = "127.0.0.1:3000", long = "listen")]
+    pub(crate) listen: SocketAddr,
+    /// The RDN sequence of the home server, which is the issuer of all certificates it issues
+    #[arg(default_value = DEFAULT_ISSUER, long = "issuer")]
+    pub(crate) issuer: String,
+}
+
+/// The subject of the ID-CSR submitted when probing a home server, if none is given by the user
+pub(crate) const DEFAULT_ACTOR_SUBJECT: &str =
+    "CN=stimmgabel,DC=polyphony,DC=chat,UID=stimmgabel@polyphony.chat,uniqueIdentifier=probe";
+
+/// The arguments of the `probe-server` command
+#[derive(Debug, Args, PartialEq, Eq, Clone)]
+pub(crate) struct ProbeArgs {
+    /// The base URL of the home server, e.g. http://127.0.0.1:3000
+    pub(crate) url: String,
+    /// The RDN sequence of the actor, for whom an ID-CSR is submitted to the home server
+    #[arg(default_value = DEFAULT_ACTOR_SUBJECT, long = "subject")]
+    pub(crate) subject: String,
+    /// Print a machine-readable report of every route to stdout instead of a summary
+    #[arg(long = "report")]
+    pub(crate) report: Option<ReportFormat>,
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq, Clone)]
+pub(crate) enum SignMode {
+    /// Sign a message, printing it as JSON in the format accepted by `verify message`
+    Message {
+        /// The message to sign
+        message: String,
+        /// Whose private key to sign with
+        #[arg(default_value_t = Target::Actor, long = "key")]
+        key: Target,
+    },
+    /// Sign a challenge string, printing it as JSON in the format accepted by `verify challenge`
+    Challenge {
+        /// The challenge string to sign
+        challenge: String,
+        /// The RFC 3339 timestamp after which the challenge string can no longer be answered.
+        /// Defaults to five minutes from now
+        #[arg(long = "expires", value_parser = parse_timestamp)]
+        expires: Option<u64>,
+        /// Whose private key to sign with
+        #[arg(default_value_t = Target::Actor, long = "key")]
+        key: Target,
+    },
 }
 
 #[derive(Debug, Subcommand, PartialEq, Eq, Clone)]
 pub(crate) enum StimmgabelMode {
     /// Verify a polyproto ID-Cert for its well-formedness and syntactical and cryptographical correctness
+    // The value may be omitted in favor of --input, while the target stays the last positional
+    #[command(allow_missing_positional = true)]
     IdCert {
-        /// The value to verify
-        value: String,
+        #[command(flatten)]
+        input: Input,
         #[arg(default_value_t = Format::Der, long = "encoding")]
         /// The format, in which the value is encoded
         encoding: Format,
         /// Who this certificate is supposed to be for
         target: Target,
+        /// The RFC 3339 timestamp at which the certificate has to be valid. Defaults to now
+        #[arg(long = "at", value_parser = parse_timestamp)]
+        at: Option<u64>,
     },
-    /// The message to verify. Must be JSON encoded
+    /// Verify a polyproto message. The message must be JSON encoded and match the following format:
+    /// {
+    ///     "message": "Any string",
+    ///     "signature": "Base64 encoded signature",
+    ///     "public_key": "Base64 encoded public key of the sender"
+    /// }.
+    /// The signature must be made over the UTF-8 bytes of "message". The JSON may be minified and
+    /// the order of the keys is unimportant
     Message {
-        /// The message to verify. Must be JSON encoded and match the following format:
-        /// {
-        ///     "message": "Any string",
-        ///     "signature": "Base64 encoded signature",
-        ///     "public_key": "Base64 encoded public key of the sender"
-        /// }.
-        /// The JSON may be minified and the order of the keys is unimportant
-        value: String,
+        #[command(flatten)]
+        input: Input,
     },
     /// Verify a polyproto Id-CSR for its well-formedness and syntactical and cryptographical correctness
+    #[command(allow_missing_positional = true)]
     IdCsr {
-        /// The value to verify
-        value: String,
+        #[command(flatten)]
+        input: Input,
         #[arg(default_value_t = Format::Der, long = "encoding")]
         /// The format, in which the value is encoded
         encoding: Format,
         /// Who this CSR is supposed to be for
         target: Target,
     },
+    /// Verify a chain of polyproto ID-Certs, from an actor certificate up to a trusted home server
+    Chain {
+        /// Files containing the ID-Certs of the chain, ordered from the end-entity certificate up
+        /// to the certificate closest to the trust anchor
+        #[arg(required = true)]
+        certificates: Vec<PathBuf>,
+        #[arg(default_value_t = Format::Der, long = "encoding")]
+        /// The format, in which the values are encoded
+        encoding: Format,
+        /// A file containing the key of the trust anchor, which has to have signed the last
+        /// certificate of the chain. Defaults to the issuer key
+        #[arg(long = "trust-anchor")]
+        trust_anchor: Option<PathBuf>,
+    },
+    /// Verify a challenge string signed by an actor. The signed challenge string must be JSON
+    /// encoded and match the following format:
+    /// {
+    ///     "challenge": "The challenge string handed out by the home server",
+    ///     "expires": UNIX timestamp after which the challenge string can no longer be answered,
+    ///     "signature": "Base64 encoded signature"
+    /// }.
+    /// The signature must be made over the UTF-8 bytes of "challenge" and has to verify under the
+    /// public key of the actor's ID-Cert
+    Challenge {
+        #[command(flatten)]
+        input: Input,
+        /// A file containing the ID-Cert of the actor who signed the challenge string
+        #[arg(long = "id-cert")]
+        id_cert: PathBuf,
+        #[arg(default_value_t = Format::Der, long = "encoding")]
+        /// The format, in which the ID-Cert is encoded
+        encoding: Format,
+        /// The RFC 3339 timestamp at which the challenge string is answered. Defaults to now
+        #[arg(long = "at", value_parser = parse_timestamp)]
+        at: Option<u64>,
+    },
+    /// Verify that a polyproto ID-Cert was issued from a given ID-CSR: The ID-Cert has to name the
+    /// subject and public key of the ID-CSR, may only grant the requested capabilities or a subset
+    /// of them and has to be signed by the issuer key
+    Issuance {
+        #[command(flatten)]
+        input: Input,
+        /// A file containing the ID-CSR the ID-Cert was issued from
+        #[arg(long = "csr")]
+        csr: PathBuf,
+        #[arg(default_value_t = Format::Der, long = "encoding")]
+        /// The format, in which the ID-Cert and the ID-CSR are encoded
+        encoding: Format,
+    },
+    /// Verify a federation ID, e.g. "flori@polyphony.chat", for its well-formedness. Given the RDN
+    /// sequence of a subject instead, the domain of the federation ID in its UID also has to match
+    /// its trailing DC components
+    FederationId {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Print the capabilities a polyproto ID-Cert grants, as decoded from its key usage and basic
+    /// constraints extensions, and check them against the required and forbidden ones. The ID-Cert
+    /// itself is only parsed, not verified
+    Capabilities {
+        #[command(flatten)]
+        input: Input,
+        #[arg(default_value_t = Format::Der, long = "encoding")]
+        /// The format, in which the value is encoded
+        encoding: Format,
+        /// Capabilities the ID-Cert has to grant, e.g. digital-signature
+        #[arg(long = "require", value_delimiter = ',')]
+        require: Vec<Capability>,
+        /// Capabilities the ID-Cert must not grant, e.g. key-cert-sign or ca
+        #[arg(long = "forbid", value_delimiter = ',')]
+        forbid: Vec<Capability>,
+    },
+    /// Verify many values at once. Every line of the input is a JSON object naming the "mode"
+    /// (id-cert, id-csr, message or federation-id), "encoding", "target" and "value" of a single
+    /// verification. One JSON result is printed per line, followed by a summary of all verifications
+    Batch {
+        /// A file containing the JSON Lines to verify. Pass `-` to read from stdin
+        #[arg(default_value = "-", long = "input", short = 'i')]
+        input: PathBuf,
+    },
+}
+
+/// The source a value is read from. Exactly one of the two has to be given
+#[derive(Debug, Args, PartialEq, Eq, Clone)]
+#[group(required = true, multiple = false)]
+pub(crate) struct Input {
+    /// The value itself
+    pub(crate) value: Option<String>,
+    /// Read the value from a file instead. Pass `-` to read from stdin
+    #[arg(long = "input", short = 'i')]
+    pub(crate) input: Option<PathBuf>,
 }
-/// The different keys that can be printed
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
-pub(crate) enum KeyChoice {
-    ActorPrivate,
-    ActorPublic,
-    HomeserverPrivate,
-    HomeserverPublic,
+
+impl Input {
+    /// Reads the raw bytes of the value from wherever the user told us to read them from
+    pub(crate) fn read(&self) -> std::io::Result<Vec<u8>> {
+        match (&self.value, &self.input) {
+            (Some(value), _) => Ok(value.as_bytes().to_vec()),
+            (None, Some(path)) if path.as_os_str() == "-" => {
+                let mut buffer = Vec::new();
+                std::io::stdin().read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+            (None, Some(path)) => std::fs::read(path),
+            // clap makes sure that one of the two is always present
+            (None, None) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "No value was given",
+            )),
+        }
+    }
 }
 
-impl ValueEnum for KeyChoice {
+/// The formats a verification report can be printed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReportFormat {
+    Json,
+}
+
+impl ValueEnum for ReportFormat {
     fn value_variants<'a>() -> &'a [Self] {
-        &[
-            KeyChoice::ActorPrivate,
-            KeyChoice::ActorPublic,
-            KeyChoice::HomeserverPrivate,
-            KeyChoice::HomeserverPublic,
-        ]
+        &[ReportFormat::Json]
     }
 
     fn to_possible_value(&self) -> Option<PossibleValue> {
         match self {
-            KeyChoice::ActorPrivate => Some(PossibleValue::new("actor-private")),
-            KeyChoice::ActorPublic => Some(PossibleValue::new("actor-public")),
-            KeyChoice::HomeserverPrivate => Some(PossibleValue::new("homeserver-private")),
-            KeyChoice::HomeserverPublic => Some(PossibleValue::new("homeserver-public")),
+            ReportFormat::Json => Some(PossibleValue::new("json")),
         }
     }
 }
 
+/// The transports `serve` can answer JSON-RPC requests over
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum Format {
-    Der,
-    Pem,
+pub(crate) enum Transport {
+    /// Read one request per line from stdin and write one response per line to stdout
+    Stdio,
 }
 
-impl ValueEnum for Format {
+impl ValueEnum for Transport {
     fn value_variants<'a>() -> &'a [Self] {
-        &[Format::Der, Format::Pem]
+        &[Transport::Stdio]
     }
 
     fn to_possible_value(&self) -> Option<PossibleValue> {
         match self {
-            Format::Der => Some(PossibleValue::new("der")),
-            Format::Pem => Some(PossibleValue::new("pem")),
+            Transport::Stdio => Some(PossibleValue::new("stdio")),
         }
     }
 }
 
-impl Display for Format {
+impl Display for Transport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Format::Der => write!(f, "der"),
-            Format::Pem => write!(f, "pem"),
+            Transport::Stdio => write!(f, "stdio"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum Target {
-    Actor,
-    Homeserver,
+/// Parse an RFC 3339 timestamp, e.g. `2024-05-01T12:00:00Z`, into seconds since the UNIX epoch
+pub(crate) fn parse_timestamp(value: &str) -> Result<u64, String> {
+    let timestamp = chrono::DateTime::parse_from_rfc3339(value).map_err(|e| e.to_string())?;
+    u64::try_from(timestamp.timestamp())
+        .map_err(|_| "Timestamps before 1970-01-01T00:00:00Z are not supported".to_string())
 }
 
-impl ValueEnum for Target {
-    fn value_variants<'a>() -> &'a [Self] {
-        &[Target::Actor, Target::Homeserver]
-    }
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
 
-    fn to_possible_value(&self) -> Option<PossibleValue> {
-        match self {
-            Target::Actor => Some(PossibleValue::new("actor")),
-            Target::Homeserver => Some(PossibleValue::new("homeserver")),
+    use clap::{CommandFactory, Parser};
+
+    use super::{CliArguments, Commands, Input, StimmgabelMode, Target, Transport};
+
+    fn verify_mode(args: &[&str]) -> StimmgabelMode {
+        match CliArguments::try_parse_from(args).unwrap().command {
+            Commands::Verify { mode, .. } => mode,
+            command => panic!("Expected verify, got {:?}", command),
         }
     }
-}
 
-impl Display for Target {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Target::Actor => write!(f, "actor"),
-            Target::Homeserver => write!(f, "homeserver"),
+    #[test]
+    fn command_line_is_consistent() {
+        CliArguments::command().debug_assert();
+    }
+
+    #[test]
+    fn target_is_the_last_positional_with_and_without_value() {
+        let StimmgabelMode::IdCert { input, target, .. } =
+            verify_mode(&["stimmgabel", "verify", "id-cert", "MIIB", "actor"])
+        else {
+            panic!("Expected id-cert")
+        };
+        assert_eq!(
+            input,
+            Input {
+                value: Some("MIIB".to_string()),
+                input: None
+            }
+        );
+        assert_eq!(target, Target::Actor);
+        let StimmgabelMode::IdCsr { input, target, .. } = verify_mode(&[
+            "stimmgabel",
+            "verify",
+            "id-csr",
+            "--input",
+            "-",
+            "homeserver",
+        ]) else {
+            panic!("Expected id-csr")
+        };
+        assert_eq!(
+            input,
+            Input {
+                value: None,
+                input: Some(PathBuf::from("-"))
+            }
+        );
+        assert_eq!(target, Target::Homeserver);
+    }
+
+    #[test]
+    fn serve_uses_stdio_unless_told_otherwise() {
+        for args in [
+            &["stimmgabel", "serve"][..],
+            &["stimmgabel", "serve", "--transport", "stdio"],
+        ] {
+            let command = CliArguments::try_parse_from(args).unwrap().command;
+            assert!(matches!(
+                command,
+                Commands::Serve {
+                    transport: Transport::Stdio
+                }
+            ));
         }
+        assert!(
+            CliArguments::try_parse_from(["stimmgabel", "serve", "--transport", "tcp"]).is_err()
+        );
     }
 }
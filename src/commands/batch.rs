@@ -0,0 +1,213 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use stimmgabel::errors::ExitCode;
+use stimmgabel::keyring::KeyRing;
+use stimmgabel::report::Report;
+
+use crate::cli::{parse_timestamp, Input, SignatureAlgorithm, StimmgabelMode};
+use crate::commands::verify::verify_input;
+
+/// A single verification, as given by a line of a batch
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BatchEntry {
+    /// The verification mode, e.g. `id-cert`
+    mode: String,
+    value: String,
+    /// The format, in which an ID-Cert or ID-CSR is encoded
+    encoding: Option<String>,
+    /// Who an ID-Cert or ID-CSR is supposed to be for
+    target: Option<String>,
+    /// The RFC 3339 timestamp at which an ID-Cert has to be valid. Defaults to now
+    at: Option<String>,
+    /// The signature algorithm a message is signed with. Defaults to the one given by --algorithm
+    algorithm: Option<String>,
+}
+
+/// The result of verifying a line of a batch
+#[derive(Debug, Serialize)]
+struct BatchResult<'a> {
+    /// The number of the line, starting at 1
+    line: usize,
+    #[serde(flatten)]
+    report: &'a Report,
+}
+
+/// The aggregate result of all verifications of a batch
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    total: usize,
+    passed: usize,
+    failed: usize,
+    /// The exit codes of all failed verifications combined, which stimmgabel exits with
+    exit_code: i32,
+}
+
+impl Summary {
+    fn add(&mut self, report: &Report) {
+        self.total += 1;
+        match report.exit_code {
+            0 => self.passed += 1,
+            exit_code => {
+                self.failed += 1;
+                self.exit_code |= exit_code;
+            }
+        }
+    }
+}
+
+/// Verify every line of the JSON Lines file `input` and print one result per line to stdout,
+/// followed by a summary. Messages are expected to be signed with the given [SignatureAlgorithm],
+/// unless their line names another one. Returns the exit code to exit with.
+pub(crate) fn verify_batch(input: &Path, keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    let reader: Box<dyn BufRead> = if input.as_os_str() == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        match std::fs::File::open(input) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Could not read {}: {}", input.display(), e);
+                return ExitCode::GARBLED_INPUT.bits();
+            }
+        }
+    };
+    let mut summary = Summary::default();
+    for (index, line) in reader.lines().enumerate() {
+        let (report, stop) = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => (verify_line(&line, keys, algorithm), false),
+            // Lines which are not valid UTF-8 can be skipped, other errors end the batch
+            Err(e) => (
+                Report::new("batch").fail("read", ExitCode::GARBLED_INPUT.bits(), &e),
+                e.kind() != ErrorKind::InvalidData,
+            ),
+        };
+        summary.add(&report);
+        let result = BatchResult {
+            line: index + 1,
+            report: &report,
+        };
+        // Unwrap is ok, since the result only consists of strings, booleans and integers
+        println!("{}", serde_json::to_string(&result).unwrap());
+        if stop {
+            break;
+        }
+    }
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({ "summary": summary })).unwrap()
+    );
+    summary.exit_code
+}
+
+/// Parse a line of a batch and verify the value it names
+fn verify_line(line: &str, keys: &KeyRing, algorithm: SignatureAlgorithm) -> Report {
+    let entry: BatchEntry = match serde_json::from_str(line) {
+        Ok(entry) => entry,
+        Err(e) => return Report::new("batch").fail("parse", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    verify_entry(&entry, keys, algorithm)
+}
+
+/// Verify the value named by a [BatchEntry]. Messages are expected to be signed with the given
+/// [SignatureAlgorithm], unless the entry names another one.
+pub(crate) fn verify_entry(
+    entry: &BatchEntry,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Report {
+    let algorithm = match entry.algorithm.as_deref() {
+        Some(name) => match SignatureAlgorithm::from_str(name, false) {
+            Ok(algorithm) => algorithm,
+            Err(e) => {
+                return Report::new(&entry.mode).fail("parse", ExitCode::INVALID_INPUT.bits(), e)
+            }
+        },
+        None => algorithm,
+    };
+    match entry.to_mode() {
+        Ok(mode) => verify_input(mode, keys, algorithm),
+        Err(e) => Report::new(&entry.mode).fail("parse", ExitCode::INVALID_INPUT.bits(), e),
+    }
+}
+
+impl BatchEntry {
+    /// The `verify` mode this entry stands for
+    fn to_mode(&self) -> Result<StimmgabelMode, String> {
+        let input = Input {
+            value: Some(self.value.clone()),
+            input: None,
+        };
+        match self.mode.as_str() {
+            "id-cert" => Ok(StimmgabelMode::IdCert {
+                input,
+                encoding: required(&self.encoding, "encoding")?,
+                target: required(&self.target, "target")?,
+                at: self.at.as_deref().map(parse_timestamp).transpose()?,
+            }),
+            "id-csr" => Ok(StimmgabelMode::IdCsr {
+                input,
+                encoding: required(&self.encoding, "encoding")?,
+                target: required(&self.target, "target")?,
+            }),
+            "message" => Ok(StimmgabelMode::Message { input }),
+            "federation-id" => Ok(StimmgabelMode::FederationId { input }),
+            mode => Err(format!(
+                "Unknown mode {}, expected one of id-cert, id-csr, message or federation-id",
+                mode
+            )),
+        }
+    }
+}
+
+/// Parse a field, which the mode of the entry requires
+fn required<T: ValueEnum>(value: &Option<String>, name: &str) -> Result<T, String> {
+    match value {
+        Some(value) => T::from_str(value, false),
+        None => Err(format!("The field {} is missing", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stimmgabel::errors::ExitCode;
+    use stimmgabel::keyring::KeyRing;
+
+    use crate::cli::SignatureAlgorithm;
+
+    use super::{verify_line, Summary};
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        let keys = KeyRing::default();
+        let verify = |line: &str| verify_line(line, &keys, SignatureAlgorithm::Ed25519);
+        assert_eq!(verify("not json").exit_code, ExitCode::GARBLED_INPUT.bits());
+        let unknown_mode = verify(r#"{"mode":"chain","value":""}"#);
+        assert_eq!(unknown_mode.mode, "chain");
+        assert_eq!(unknown_mode.exit_code, ExitCode::INVALID_INPUT.bits());
+        let missing_target = verify(r#"{"mode":"id-cert","encoding":"pem","value":""}"#);
+        assert_eq!(missing_target.exit_code, ExitCode::INVALID_INPUT.bits());
+    }
+
+    #[test]
+    fn summary_combines_exit_codes() {
+        let keys = KeyRing::default();
+        let mut summary = Summary::default();
+        for line in [
+            r#"{"mode":"id-cert","encoding":"der-hex","target":"actor","value":"zz"}"#,
+            r#"{"mode":"message","value":"{}"}"#,
+        ] {
+            summary.add(&verify_line(line, &keys, SignatureAlgorithm::Ed25519));
+        }
+        assert_eq!(summary.total, 2);
+        assert_eq!(summary.failed, 2);
+        assert_ne!(summary.exit_code & ExitCode::GARBLED_INPUT.bits(), 0);
+    }
+}
@@ -0,0 +1,74 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::Write;
+use std::str::FromStr;
+
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::RdnSequence;
+use stimmgabel::algorithm::Algorithm;
+use stimmgabel::encoding::encode_der;
+use stimmgabel::errors::{conversion_error_to_exit_code, ExitCode};
+use stimmgabel::keyring::KeyRing;
+use stimmgabel::with_algorithm;
+
+use crate::cli::{CsrAction, Format, SignatureAlgorithm, Target};
+
+/// Perform the given [CsrAction], signing with the given [SignatureAlgorithm]. Returns the exit code
+/// to exit with.
+pub(crate) fn csr(action: CsrAction, keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    match action {
+        CsrAction::Create {
+            subject,
+            target,
+            key,
+            encoding,
+        } => match with_algorithm!(
+            algorithm,
+            A => create_csr::<A>(&subject, target, keys, key, encoding)
+        ) {
+            Ok(encoded) => {
+                // A failing write to stdout cannot be reported anywhere else
+                std::io::stdout().write_all(&encoded).unwrap_or(());
+                0
+            }
+            Err((code, message)) => {
+                eprintln!("{}", message);
+                code
+            }
+        },
+    }
+}
+
+/// Create an ID-CSR for the given subject, requesting the default capabilities of the given
+/// [Target], and encode it in the given [Format].
+pub(crate) fn create_csr<A: Algorithm>(
+    subject: &str,
+    target: Target,
+    keys: &KeyRing,
+    key: Target,
+    encoding: Format,
+) -> Result<Vec<u8>, (i32, String)> {
+    let subject = RdnSequence::from_str(subject).map_err(|e| {
+        (
+            ExitCode::INVALID_INPUT.bits(),
+            format!("Invalid subject: {}", e),
+        )
+    })?;
+    let private_key = keys
+        .signing_key::<A>(key)
+        .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e))?;
+    let capabilities = match target {
+        Target::Actor => Capabilities::default_actor(),
+        Target::Homeserver => Capabilities::default_home_server(),
+    };
+    let csr = IdCsr::<A::Signature, A::PublicKey>::new(&subject, &private_key, &capabilities)
+        .map_err(|e| (conversion_error_to_exit_code(&e), e.to_string()))?;
+    let der = csr
+        .to_der()
+        .map_err(|e| (conversion_error_to_exit_code(&e), e.to_string()))?;
+    encode_der(&der, "CERTIFICATE REQUEST", encoding)
+        .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e))
+}
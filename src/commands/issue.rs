@@ -0,0 +1,133 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::Write;
+use std::str::FromStr;
+
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::der::asn1::Uint;
+use polyproto::key::PublicKey;
+use polyproto::RdnSequence;
+use stimmgabel::algorithm::{check_signature, detect_algorithm, Algorithm, DetectedAlgorithm};
+use stimmgabel::encoding::{decode_der, encode_der, unix_now, x509_time};
+use stimmgabel::errors::{conversion_error_to_exit_code, ExitCode};
+use stimmgabel::keyring::KeyRing;
+use stimmgabel::with_algorithm;
+use x509_cert::time::Validity;
+
+use crate::cli::{IssueArgs, SignatureAlgorithm, Target};
+
+/// The length of the validity period of issued certificates, if no end is given by the user
+const DEFAULT_VALIDITY_SECONDS: u64 = 60 * 60 * 24 * 365;
+
+/// Read an ID-CSR, validate it and issue an ID-Cert from it, which is printed to stdout. Returns the
+/// exit code to exit with. The issued ID-Cert uses the [SignatureAlgorithm] named in the ID-CSR's
+/// algorithm identifiers, or the given one, if they cannot be read.
+pub(crate) fn issue(args: &IssueArgs, keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    match issue_encoded(args, keys, algorithm) {
+        Ok(encoded) => {
+            // A failing write to stdout cannot be reported anywhere else
+            std::io::stdout().write_all(&encoded).unwrap_or(());
+            0
+        }
+        Err((code, message)) => {
+            eprintln!("{}", message);
+            code
+        }
+    }
+}
+
+/// Read an ID-CSR, validate it and issue an ID-Cert from it, encoded in the output encoding of
+/// `args`. See [issue] for how the [SignatureAlgorithm] is chosen.
+pub(crate) fn issue_encoded(
+    args: &IssueArgs,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Result<Vec<u8>, (i32, String)> {
+    let der = read_csr(args)?;
+    let algorithm = match detect_algorithm(&der) {
+        DetectedAlgorithm::Supported(algorithm) => algorithm,
+        DetectedAlgorithm::Unsupported(e) => {
+            return Err((ExitCode::UNSUPPORTED_ALGORITHM.bits(), e))
+        }
+        DetectedAlgorithm::Unreadable => algorithm,
+    };
+    with_algorithm!(algorithm, A => issue_certificate::<A>(&der, args, keys))
+}
+
+/// Read the ID-CSR to issue a certificate from and decode it to DER
+fn read_csr(args: &IssueArgs) -> Result<Vec<u8>, (i32, String)> {
+    let value = args
+        .input
+        .read()
+        .map_err(|e| (ExitCode::GARBLED_INPUT.bits(), e.to_string()))?;
+    decode_der(&value, args.encoding).map_err(|e| (ExitCode::GARBLED_INPUT.bits(), e))
+}
+
+fn issue_certificate<A: Algorithm>(
+    der: &[u8],
+    args: &IssueArgs,
+    keys: &KeyRing,
+) -> Result<Vec<u8>, (i32, String)> {
+    let csr = check_signature::<A>(der)
+        .and_then(|_| IdCsr::<A::Signature, A::PublicKey>::from_der(der))
+        .map_err(|e| (conversion_error_to_exit_code(&e), e.to_string()))?;
+    // The CSR has to be signed by the private key belonging to the public key it contains
+    let signature_data = csr
+        .signature_data()
+        .map_err(|e| (conversion_error_to_exit_code(&e), e.to_string()))?;
+    csr.inner_csr
+        .subject_public_key
+        .verify_signature(&csr.signature, &signature_data)
+        .map_err(|e| (ExitCode::BAD_SIGNATURE.bits(), e.to_string()))?;
+    match args.target {
+        Target::Actor => csr.validate_actor(),
+        Target::Homeserver => csr.validate_home_server(),
+    }
+    .map_err(|e| (conversion_error_to_exit_code(&e), e.to_string()))?;
+
+    let issuer = RdnSequence::from_str(&args.issuer).map_err(|e| {
+        (
+            ExitCode::INVALID_INPUT.bits(),
+            format!("Invalid issuer: {}", e),
+        )
+    })?;
+    let serial_number = Uint::new(&args.serial.to_be_bytes())
+        .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e.to_string()))?;
+    let validity = validity(args.not_before, args.not_after)?;
+    let private_key = keys
+        .signing_key::<A>(Target::Homeserver)
+        .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e))?;
+
+    let certificate = match args.target {
+        Target::Actor => IdCert::from_actor_csr(csr, &private_key, serial_number, issuer, validity),
+        Target::Homeserver => {
+            IdCert::from_home_server_csr(csr, &private_key, serial_number, issuer, validity)
+        }
+    }
+    .map_err(|e| (conversion_error_to_exit_code(&e), e.to_string()))?;
+    let certificate_der = certificate
+        .to_der()
+        .map_err(|e| (conversion_error_to_exit_code(&e), e.to_string()))?;
+    encode_der(&certificate_der, "CERTIFICATE", args.output_encoding)
+        .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e))
+}
+
+/// Build the validity period of the certificate to issue from the optional bounds given by the user
+fn validity(not_before: Option<u64>, not_after: Option<u64>) -> Result<Validity, (i32, String)> {
+    let not_before = not_before.unwrap_or_else(unix_now);
+    let not_after = not_after.unwrap_or(not_before + DEFAULT_VALIDITY_SECONDS);
+    if not_after < not_before {
+        return Err((
+            ExitCode::INVALID_INPUT.bits(),
+            "The end of the validity period lies before its start".to_string(),
+        ));
+    }
+    let invalid_time = |e: polyproto::der::Error| (ExitCode::INVALID_INPUT.bits(), e.to_string());
+    Ok(Validity {
+        not_before: x509_time(not_before).map_err(invalid_time)?,
+        not_after: x509_time(not_after).map_err(invalid_time)?,
+    })
+}
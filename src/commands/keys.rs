@@ -2,16 +2,11 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use stimmgabel::algorithm::Algorithm;
+
 use crate::cli::KeyChoice;
-use crate::*;
 
-/// Prints the key specified by the user with the [KeyChoice] enum
-pub(crate) fn print_key(choice: &KeyChoice) {
-    let key = match choice {
-        KeyChoice::ActorPrivate => ED25519_PRIVATE_ACTOR,
-        KeyChoice::ActorPublic => ED25519_PUBLIC_ACTOR,
-        KeyChoice::HomeserverPrivate => ED25519_PRIVATE_HOMESERVER,
-        KeyChoice::HomeserverPublic => ED25519_PUBLIC_HOMESERVER,
-    };
-    println!("{}", key);
+/// Prints the built-in key of the given [Algorithm] specified by the user with the [KeyChoice] enum
+pub(crate) fn print_key<A: Algorithm>(choice: &KeyChoice) {
+    println!("{}", A::builtin_key_pem(*choice));
 }
@@ -2,5 +2,46 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub(crate) mod batch;
+pub(crate) mod csr;
+pub(crate) mod issue;
 pub(crate) mod keys;
+pub(crate) mod probe;
+pub(crate) mod serve;
+pub(crate) mod server;
+pub(crate) mod sign;
 pub(crate) mod verify;
+
+use colored::Colorize;
+use stimmgabel::report::{GrantedCapabilities, Report};
+
+/// Print whether all checks of a report passed or, if not, which check failed and why
+pub(crate) fn print_summary(report: &Report) {
+    match report.checks.iter().find(|check| !check.passed) {
+        None => println!("{} {}", "PASS".green(), report.mode),
+        Some(check) => println!(
+            "{} {}: Check {} failed: {}",
+            "FAIL".red(),
+            report.mode,
+            check.name,
+            check.error.as_deref().unwrap_or("unknown error")
+        ),
+    }
+}
+
+/// Print the key usages an ID-Cert grants, whether it may act as a CA and how long the chain below
+/// it may be
+pub(crate) fn print_capabilities(capabilities: &GrantedCapabilities) {
+    let key_usage = if capabilities.key_usage.is_empty() {
+        "none".to_string()
+    } else {
+        capabilities.key_usage.join(", ")
+    };
+    let path_length = match capabilities.path_length {
+        Some(path_length) => path_length.to_string(),
+        None => "unlimited".to_string(),
+    };
+    println!("key usage: {}", key_usage);
+    println!("ca: {}", capabilities.ca);
+    println!("path length: {}", path_length);
+}
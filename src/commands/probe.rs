@@ -0,0 +1,451 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+
+use polyproto::key::PublicKey;
+use polyproto::RdnSequence;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use stimmgabel::algorithm::Algorithm;
+use stimmgabel::encoding::unix_now;
+use stimmgabel::errors::ExitCode;
+use stimmgabel::keyring::{KeyFile, KeyRing};
+use stimmgabel::polyproto::challenge::CHALLENGE_LENGTH;
+use stimmgabel::polyproto::federation_id::FederationId;
+use stimmgabel::report::{Check, Report};
+use stimmgabel::verify::{verify_id_cert, verify_issuance, VerificationFailure};
+use stimmgabel::with_algorithm;
+
+use crate::cli::{Format, ProbeArgs, ReportFormat, SignatureAlgorithm, Target};
+use crate::commands::csr::create_csr;
+use crate::commands::print_summary;
+use crate::commands::server::{
+    IdCsrSubmission, ACTOR_PUBLIC_KEY_ROUTE, CHALLENGE_ROUTE, SERVER_ID_CERT_ROUTE,
+    SERVER_PUBLIC_KEY_ROUTE, SESSION_ID_CERT_ROUTE,
+};
+use crate::commands::sign::sign_challenge;
+
+#[derive(Debug, Deserialize)]
+struct ChallengeString {
+    challenge: String,
+    expires: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdCertToken {
+    id_cert: String,
+    token: String,
+}
+
+/// Drive the home server at the URL given in `args` through the core routes of the polyproto HTTP
+/// API and print a report per route. The ID-CSR submitted to the home server is signed with the
+/// actor key of the given [SignatureAlgorithm]. Returns the exit code to exit with, which combines
+/// the exit codes of all routes.
+pub(crate) fn probe_server(args: &ProbeArgs, keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    let client = match Client::builder().build() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Could not create the HTTP client: {}", e);
+            return ExitCode::INVALID_INPUT.bits();
+        }
+    };
+    let reports = probe(&client, args, keys, algorithm);
+    match args.report {
+        // Unwrap is ok, since the reports only consist of strings, booleans and integers
+        Some(ReportFormat::Json) => println!("{}", serde_json::to_string(&reports).unwrap()),
+        None => reports.iter().for_each(print_summary),
+    }
+    reports
+        .iter()
+        .fold(0, |exit_code, report| exit_code | report.exit_code)
+}
+
+/// Probe every route, in the order a client would call them. Each route gets a [Report], whose mode
+/// is the route.
+fn probe(
+    client: &Client,
+    args: &ProbeArgs,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Vec<Report> {
+    let base = args.url.trim_end_matches('/');
+    let (challenge_report, challenge) = probe_challenge(client, base);
+    let (public_key_report, server_key) = probe_public_key(client, base);
+    vec![
+        challenge_report,
+        public_key_report,
+        probe_server_id_cert(client, base, server_key.as_ref()),
+        probe_session_id_cert(
+            client,
+            base,
+            args,
+            keys,
+            challenge.as_ref(),
+            server_key.as_ref(),
+            algorithm,
+        ),
+        probe_actor_public_key(client, base, args, keys, algorithm),
+    ]
+}
+
+/// Send a request and return the body of a successful response. If the request fails, the name of
+/// the failed check, its exit code and the reason are returned.
+fn send(
+    report: &mut Report,
+    request: RequestBuilder,
+) -> Result<String, (&'static str, i32, String)> {
+    let response = request
+        .send()
+        .map_err(|e| ("request", ExitCode::INVALID_INPUT.bits(), e.to_string()))?;
+    report.pass("request");
+    let status = response.status();
+    if !status.is_success() {
+        return Err((
+            "status",
+            ExitCode::INVALID_INPUT.bits(),
+            format!("The home server answered with {}", status),
+        ));
+    }
+    report.pass("status");
+    response
+        .text()
+        .map_err(|e| ("body", ExitCode::GARBLED_INPUT.bits(), e.to_string()))
+}
+
+/// Fetch a challenge string, which is answered when requesting an actor ID-Cert
+fn probe_challenge(client: &Client, base: &str) -> (Report, Option<ChallengeString>) {
+    let mut report = Report::new(&format!("GET {}", CHALLENGE_ROUTE));
+    let body = match send(
+        &mut report,
+        client.get(format!("{}{}", base, CHALLENGE_ROUTE)),
+    ) {
+        Ok(body) => body,
+        Err((check, exit_code, error)) => return (report.fail(check, exit_code, error), None),
+    };
+    let challenge: ChallengeString = match serde_json::from_str(&body) {
+        Ok(challenge) => challenge,
+        Err(e) => {
+            return (
+                report.fail("parse", ExitCode::GARBLED_INPUT.bits(), e),
+                None,
+            )
+        }
+    };
+    report.pass("parse");
+    (check_challenge(report, &challenge), Some(challenge))
+}
+
+/// Check that a challenge string can be answered: It has to be long enough and not expired yet
+fn check_challenge(mut report: Report, challenge: &ChallengeString) -> Report {
+    let length = challenge.challenge.chars().count();
+    if !CHALLENGE_LENGTH.contains(&length) {
+        return report.fail(
+            "length",
+            ExitCode::CONSTRAINT_VIOLATION.bits(),
+            format!(
+                "Challenge strings have to be {} to {} characters long, got {}",
+                CHALLENGE_LENGTH.start(),
+                CHALLENGE_LENGTH.end(),
+                length
+            ),
+        );
+    }
+    report.pass("length");
+    if challenge.expires <= unix_now() {
+        return report.fail(
+            "expires",
+            ExitCode::EXPIRED.bits(),
+            "The challenge string has already expired",
+        );
+    }
+    report.pass("expires");
+    report
+}
+
+/// Fetch the public key of the home server, which is needed to verify the ID-Certs it issues
+fn probe_public_key(client: &Client, base: &str) -> (Report, Option<KeyFile>) {
+    let url = format!("{}{}", base, SERVER_PUBLIC_KEY_ROUTE);
+    let mut report = Report::new(&format!("GET {}", SERVER_PUBLIC_KEY_ROUTE));
+    let body = match send(&mut report, client.get(&url)) {
+        Ok(body) => body,
+        Err((check, exit_code, error)) => return (report.fail(check, exit_code, error), None),
+    };
+    match KeyFile::parse(body.into_bytes(), &url) {
+        Ok(key) => {
+            report.pass("parse");
+            (report, Some(key))
+        }
+        Err(e) => (
+            report.fail("parse", ExitCode::GARBLED_INPUT.bits(), e),
+            None,
+        ),
+    }
+}
+
+/// Fetch the ID-Cert of the home server, which has to be signed with its public key
+fn probe_server_id_cert(client: &Client, base: &str, server_key: Option<&KeyFile>) -> Report {
+    let mut report = Report::new(&format!("GET {}", SERVER_ID_CERT_ROUTE));
+    let body = match send(
+        &mut report,
+        client.get(format!("{}{}", base, SERVER_ID_CERT_ROUTE)),
+    ) {
+        Ok(body) => body,
+        Err((check, exit_code, error)) => return report.fail(check, exit_code, error),
+    };
+    let keys = match server_key {
+        Some(key) => KeyRing::default().with_issuer_key(key.clone()),
+        None => return unavailable_key(report),
+    };
+    merge(
+        report,
+        verify_id_cert(
+            body.as_bytes(),
+            Format::Pem,
+            Target::Homeserver,
+            unix_now(),
+            &keys,
+        ),
+    )
+}
+
+/// Submit an ID-CSR signed with the actor key, along with the answer to the challenge string, and
+/// verify the ID-Cert issued from it
+fn probe_session_id_cert(
+    client: &Client,
+    base: &str,
+    args: &ProbeArgs,
+    keys: &KeyRing,
+    challenge: Option<&ChallengeString>,
+    server_key: Option<&KeyFile>,
+    algorithm: SignatureAlgorithm,
+) -> Report {
+    let mut report = Report::new(&format!("POST {}", SESSION_ID_CERT_ROUTE));
+    let csr = match with_algorithm!(
+        algorithm,
+        A => create_csr::<A>(&args.subject, Target::Actor, keys, Target::Actor, Format::Pem)
+    ) {
+        Ok(csr) => csr,
+        Err((exit_code, error)) => return report.fail("csr", exit_code, error),
+    };
+    report.pass("csr");
+    let Some(challenge) = challenge else {
+        return report.fail(
+            "challenge",
+            ExitCode::INVALID_INPUT.bits(),
+            format!(
+                "No challenge string could be fetched from {}",
+                CHALLENGE_ROUTE
+            ),
+        );
+    };
+    let (challenge, expires) = (challenge.challenge.clone(), challenge.expires);
+    let challenge = match with_algorithm!(
+        algorithm,
+        A => sign_challenge::<A>(challenge, expires, keys, Target::Actor)
+    ) {
+        Ok(challenge) => challenge,
+        Err((exit_code, error)) => return report.fail("challenge", exit_code, error),
+    };
+    report.pass("challenge");
+    let submission = IdCsrSubmission {
+        csr: String::from_utf8_lossy(&csr).into_owned(),
+        challenge,
+    };
+    let request = client
+        .post(format!("{}{}", base, SESSION_ID_CERT_ROUTE))
+        .header(CONTENT_TYPE, "application/json")
+        // Unwrap is ok, since the submission only consists of strings and integers
+        .body(serde_json::to_string(&submission).unwrap());
+    let body = match send(&mut report, request) {
+        Ok(body) => body,
+        Err((check, exit_code, error)) => return report.fail(check, exit_code, error),
+    };
+    let response: IdCertToken = match serde_json::from_str(&body) {
+        Ok(response) => response,
+        Err(e) => return report.fail("parse", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("parse");
+    if response.token.is_empty() {
+        return report.fail(
+            "token",
+            ExitCode::CONSTRAINT_VIOLATION.bits(),
+            "The home server did not hand out a session token",
+        );
+    }
+    report.pass("token");
+    let verification_keys = match server_key {
+        Some(key) => keys.clone().with_issuer_key(key.clone()),
+        None => return unavailable_key(report),
+    };
+    let id_cert = response.id_cert.as_bytes();
+    let report = merge(
+        report,
+        verify_id_cert(
+            id_cert,
+            Format::Pem,
+            Target::Actor,
+            unix_now(),
+            &verification_keys,
+        ),
+    );
+    if report.exit_code != 0 {
+        return report;
+    }
+    // The ID-Cert has to reflect the ID-CSR it was issued from
+    merge(
+        report,
+        verify_issuance(id_cert, &csr, Format::Pem, &verification_keys),
+    )
+}
+
+/// Look up the public key of the actor the ID-Cert was issued to, which has to be the actor key
+fn probe_actor_public_key(
+    client: &Client,
+    base: &str,
+    args: &ProbeArgs,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Report {
+    let mut report = Report::new(&format!("GET {}/<federation ID>", ACTOR_PUBLIC_KEY_ROUTE));
+    let federation_id = RdnSequence::from_str(&args.subject)
+        .map_err(|e| e.to_string())
+        .and_then(|subject| FederationId::from_subject(&subject).map_err(|e| e.to_string()));
+    let federation_id = match federation_id {
+        Ok(Some(federation_id)) => federation_id,
+        Ok(None) => {
+            return report.fail(
+                "federation_id",
+                ExitCode::INVALID_INPUT.bits(),
+                format!("The subject {} does not name a federation ID", args.subject),
+            )
+        }
+        Err(e) => return report.fail("federation_id", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    report.pass("federation_id");
+    let url = format!("{}{}/{}", base, ACTOR_PUBLIC_KEY_ROUTE, federation_id);
+    let body = match send(&mut report, client.get(&url)) {
+        Ok(body) => body,
+        Err((check, exit_code, error)) => return report.fail(check, exit_code, error),
+    };
+    let key = match KeyFile::parse(body.into_bytes(), &url) {
+        Ok(key) => key,
+        Err(e) => return report.fail("parse", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("parse");
+    match with_algorithm!(algorithm, A => is_actor_key::<A>(&key, keys)) {
+        Ok(true) => {
+            report.pass("public_key");
+            report
+        }
+        Ok(false) => report.fail(
+            "public_key",
+            ExitCode::BAD_PUBLIC_KEY.bits(),
+            "The home server returned another key than the one the ID-CSR was submitted with",
+        ),
+        Err(e) => report.fail("public_key", ExitCode::BAD_PUBLIC_KEY.bits(), e),
+    }
+}
+
+fn is_actor_key<A: Algorithm>(key: &KeyFile, keys: &KeyRing) -> Result<bool, String> {
+    let key = key.verifying_key::<A>()?.public_key_info();
+    let actor_key = keys.verifying_key::<A>(Target::Actor)?.public_key_info();
+    Ok(key.public_key_bitstring == actor_key.public_key_bitstring)
+}
+
+fn unavailable_key(report: Report) -> Report {
+    report.fail(
+        "issuer_key",
+        ExitCode::INVALID_INPUT.bits(),
+        format!(
+            "The public key of the home server could not be fetched from {}",
+            SERVER_PUBLIC_KEY_ROUTE
+        ),
+    )
+}
+
+/// Append the checks of a verification to the report of a route
+fn merge(mut report: Report, verification: Result<Report, VerificationFailure>) -> Report {
+    let verification = verification.unwrap_or_else(|failure| failure.report);
+    report.algorithm = verification.algorithm;
+    report.subject = verification.subject;
+    report.issuer = verification.issuer;
+    report
+        .checks
+        .extend(verification.checks.into_iter().map(|check| Check {
+            name: format!("verify.{}", check.name),
+            ..check
+        }));
+    report.exit_code = verification.exit_code;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::blocking::Client;
+    use stimmgabel::errors::ExitCode;
+    use stimmgabel::keyring::KeyRing;
+
+    use crate::cli::{
+        ProbeArgs, ServerArgs, SignatureAlgorithm, DEFAULT_ACTOR_SUBJECT, DEFAULT_ISSUER,
+    };
+    use crate::commands::server::{router, ServerState};
+
+    use super::probe;
+
+    fn probe_args(url: String) -> ProbeArgs {
+        ProbeArgs {
+            url,
+            subject: DEFAULT_ACTOR_SUBJECT.to_string(),
+            report: None,
+        }
+    }
+
+    #[test]
+    fn reference_home_server_conforms() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let args = ServerArgs {
+            listen: listener.local_addr().unwrap(),
+            issuer: DEFAULT_ISSUER.to_string(),
+        };
+        let state = ServerState::new(&args, KeyRing::default(), SignatureAlgorithm::Ed25519);
+        let app = router(Arc::new(state.unwrap()));
+        runtime.spawn(async move { axum::serve(listener, app).await });
+
+        let reports = probe(
+            &Client::new(),
+            &probe_args(format!("http://{}", args.listen)),
+            &KeyRing::default(),
+            SignatureAlgorithm::Ed25519,
+        );
+        assert_eq!(reports.len(), 5);
+        for report in reports {
+            assert_eq!(report.exit_code, 0, "{:?}", report);
+        }
+    }
+
+    #[test]
+    fn unreachable_home_server_fails_every_route() {
+        // Binding to port 0 and closing the listener again yields a port nobody listens on
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let reports = probe(
+            &Client::new(),
+            &probe_args(format!("http://{}", address)),
+            &KeyRing::default(),
+            SignatureAlgorithm::Ed25519,
+        );
+        for report in reports {
+            assert_eq!(report.exit_code, ExitCode::INVALID_INPUT.bits());
+        }
+    }
+}
@@ -0,0 +1,593 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::{BufRead, Write};
+
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use stimmgabel::algorithm::Algorithm;
+use stimmgabel::encoding::{decode_base64, unix_now};
+use stimmgabel::errors::ExitCode;
+use stimmgabel::keyring::{KeyFile, KeyRing};
+use stimmgabel::polyproto::challenge::CHALLENGE_VALIDITY_SECONDS;
+use stimmgabel::verify::{verify_capabilities, verify_chain, verify_challenge, verify_issuance};
+use stimmgabel::with_algorithm;
+
+use crate::cli::{
+    parse_timestamp, Capability, Format, Input, IssueArgs, KeyChoice, SignatureAlgorithm, Target,
+    Transport, DEFAULT_ISSUER,
+};
+use crate::commands::batch::{verify_entry, BatchEntry};
+use crate::commands::csr::create_csr;
+use crate::commands::issue::issue_encoded;
+use crate::commands::sign::{sign_challenge, sign_message};
+
+/// Error codes defined by the JSON-RPC 2.0 specification
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+/// The error code of methods which failed. The exit code stimmgabel would have exited with is
+/// given in the data of the error
+const METHOD_FAILED: i32 = -32000;
+
+/// A JSON-RPC 2.0 request. Requests without an id are notifications, which are not answered
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Value>,
+}
+
+/// Deserialize a field which is present, even if it is `null`. Absent fields are `None`
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    fn invalid_params(message: impl ToString) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    /// A method failed in the same way the corresponding command would have failed
+    fn failed((exit_code, message): (i32, String)) -> Self {
+        Self {
+            code: METHOD_FAILED,
+            message,
+            data: Some(json!({ "exit_code": exit_code })),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChainParams {
+    values: Vec<String>,
+    encoding: String,
+    /// The PEM or base64 encoded DER public key of the trust anchor
+    trust_anchor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChallengeParams {
+    value: String,
+    id_cert: String,
+    encoding: String,
+    at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IssuanceParams {
+    value: String,
+    csr: String,
+    encoding: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CapabilitiesParams {
+    value: String,
+    encoding: String,
+    #[serde(default)]
+    require: Vec<String>,
+    #[serde(default)]
+    forbid: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignChallengeParams {
+    challenge: String,
+    expires: Option<String>,
+    key: Option<String>,
+    algorithm: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SignMessageParams {
+    message: String,
+    key: Option<String>,
+    algorithm: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IssueParams {
+    value: String,
+    encoding: String,
+    output_encoding: Option<String>,
+    target: String,
+    serial: Option<u64>,
+    issuer: Option<String>,
+    not_before: Option<String>,
+    not_after: Option<String>,
+    algorithm: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateCsrParams {
+    subject: String,
+    target: String,
+    key: Option<String>,
+    encoding: Option<String>,
+    algorithm: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyParams {
+    key: String,
+    algorithm: Option<String>,
+}
+
+/// Answer JSON-RPC 2.0 requests received over the given [Transport], until it is closed. Returns
+/// the exit code to exit with.
+pub(crate) fn serve(transport: Transport, keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    match transport {
+        Transport::Stdio => serve_stdio(keys, algorithm),
+    }
+}
+
+/// Answer JSON-RPC 2.0 requests read line by line from stdin, until stdin is closed. Every response
+/// is written as a single line to stdout. Returns the exit code to exit with.
+fn serve_stdio(keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Could not read from stdin: {}", e);
+                return ExitCode::GARBLED_INPUT.bits();
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle_line(&line, keys, algorithm) {
+            if let Err(e) = writeln!(stdout, "{}", response).and_then(|_| stdout.flush()) {
+                eprintln!("Could not write to stdout: {}", e);
+                return ExitCode::INVALID_INPUT.bits();
+            }
+        }
+    }
+    0
+}
+
+/// Handle a line containing a single request or a batch of requests and return the response to it,
+/// if there is one
+fn handle_line(line: &str, keys: &KeyRing, algorithm: SignatureAlgorithm) -> Option<Value> {
+    match serde_json::from_str(line) {
+        Ok(Value::Array(requests)) if requests.is_empty() => Some(response(
+            Value::Null,
+            Err(RpcError::new(INVALID_REQUEST, "Empty batch")),
+        )),
+        Ok(Value::Array(requests)) => {
+            let responses: Vec<Value> = requests
+                .into_iter()
+                .filter_map(|request| handle_request(request, keys, algorithm))
+                .collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        Ok(request) => handle_request(request, keys, algorithm),
+        Err(e) => Some(response(Value::Null, Err(RpcError::new(PARSE_ERROR, e)))),
+    }
+}
+
+fn handle_request(request: Value, keys: &KeyRing, algorithm: SignatureAlgorithm) -> Option<Value> {
+    let request: Request = match serde_json::from_value(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(_) => {
+            let error = RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported");
+            return Some(response(Value::Null, Err(error)));
+        }
+        Err(e) => {
+            return Some(response(
+                Value::Null,
+                Err(RpcError::new(INVALID_REQUEST, e)),
+            ))
+        }
+    };
+    let result = call(&request.method, request.params, keys, algorithm);
+    request.id.map(|id| response(id, result))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+/// Call the method of the given name. `algorithm` is used by all methods whose parameters do not
+/// name a signature algorithm.
+fn call(
+    method: &str,
+    params: Value,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Result<Value, RpcError> {
+    match method {
+        "verify.id-cert" => verify("id-cert", params, keys, algorithm),
+        "verify.id-csr" => verify("id-csr", params, keys, algorithm),
+        "verify.message" => verify("message", params, keys, algorithm),
+        "verify.federation-id" => verify("federation-id", params, keys, algorithm),
+        "verify.chain" => verify_chain_values(parse_params(params)?, keys),
+        "verify.challenge" => verify_signed_challenge(parse_params(params)?),
+        "verify.issuance" => verify_issued_id_cert(parse_params(params)?, keys),
+        "verify.capabilities" => verify_granted_capabilities(parse_params(params)?),
+        "sign.message" => sign(parse_params(params)?, keys, algorithm),
+        "sign.challenge" => sign_challenge_string(parse_params(params)?, keys, algorithm),
+        "issue" => issue(parse_params(params)?, keys, algorithm),
+        "csr.create" => create(parse_params(params)?, keys, algorithm),
+        "keys.get" => key(parse_params(params)?, algorithm),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        )),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(RpcError::invalid_params)
+}
+
+fn parse<T: ValueEnum>(value: &str) -> Result<T, RpcError> {
+    T::from_str(value, false).map_err(RpcError::invalid_params)
+}
+
+/// Parse an optional parameter, falling back to `default`
+fn optional<T: ValueEnum>(value: &Option<String>, default: T) -> Result<T, RpcError> {
+    match value {
+        Some(value) => parse(value),
+        None => Ok(default),
+    }
+}
+
+/// Turn an encoded value into a JSON string. Raw DER is binary and therefore not supported
+fn encoded_value(encoded: Vec<u8>) -> Result<Value, RpcError> {
+    String::from_utf8(encoded)
+        .map(|value| json!({ "value": value }))
+        .map_err(|_| {
+            RpcError::invalid_params("Raw DER cannot be returned, use der-base64, der-hex or pem")
+        })
+}
+
+/// Verify a value like the corresponding line of `verify batch` and return the report. Failed
+/// verifications are results, not errors.
+fn verify(
+    mode: &str,
+    mut params: Value,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Result<Value, RpcError> {
+    match params.as_object_mut() {
+        Some(fields) => fields.insert("mode".to_string(), Value::from(mode)),
+        None => return Err(RpcError::invalid_params("Parameters have to be an object")),
+    };
+    let entry: BatchEntry = parse_params(params)?;
+    // Unwrap is ok, since the report only consists of strings, booleans and integers
+    Ok(serde_json::to_value(verify_entry(&entry, keys, algorithm)).unwrap())
+}
+
+fn verify_chain_values(params: ChainParams, keys: &KeyRing) -> Result<Value, RpcError> {
+    let encoding = parse(&params.encoding)?;
+    let keys = match params.trust_anchor.as_deref() {
+        Some(trust_anchor) => keys
+            .clone()
+            .with_issuer_key(trust_anchor_key(trust_anchor)?),
+        None => keys.clone(),
+    };
+    let values: Vec<Vec<u8>> = params.values.into_iter().map(String::into_bytes).collect();
+    let report =
+        verify_chain(&values, encoding, None, &keys).unwrap_or_else(|failure| failure.report);
+    // Unwrap is ok, since the report only consists of strings, booleans and integers
+    Ok(serde_json::to_value(report).unwrap())
+}
+
+/// Read a trust anchor given inline, either as PEM encoded key or as base64 encoded DER
+fn trust_anchor_key(trust_anchor: &str) -> Result<KeyFile, RpcError> {
+    let contents = if trust_anchor.trim_start().starts_with("-----BEGIN") {
+        trust_anchor.as_bytes().to_vec()
+    } else {
+        decode_base64(trust_anchor).map_err(|e| {
+            RpcError::invalid_params(format!(
+                "The trust anchor is neither PEM nor base64 encoded DER: {}",
+                e
+            ))
+        })?
+    };
+    KeyFile::parse(contents, "trust_anchor").map_err(RpcError::invalid_params)
+}
+
+fn verify_signed_challenge(params: ChallengeParams) -> Result<Value, RpcError> {
+    let encoding = parse(&params.encoding)?;
+    let at = match params.at.as_deref() {
+        Some(at) => parse_timestamp(at).map_err(RpcError::invalid_params)?,
+        None => unix_now(),
+    };
+    let report = verify_challenge(
+        params.value.as_bytes(),
+        params.id_cert.as_bytes(),
+        encoding,
+        at,
+    )
+    .unwrap_or_else(|failure| failure.report);
+    // Unwrap is ok, since the report only consists of strings, booleans and integers
+    Ok(serde_json::to_value(report).unwrap())
+}
+
+fn verify_issued_id_cert(params: IssuanceParams, keys: &KeyRing) -> Result<Value, RpcError> {
+    let encoding = parse(&params.encoding)?;
+    let report = verify_issuance(
+        params.value.as_bytes(),
+        params.csr.as_bytes(),
+        encoding,
+        keys,
+    )
+    .unwrap_or_else(|failure| failure.report);
+    // Unwrap is ok, since the report only consists of strings, booleans and integers
+    Ok(serde_json::to_value(report).unwrap())
+}
+
+fn verify_granted_capabilities(params: CapabilitiesParams) -> Result<Value, RpcError> {
+    let encoding = parse(&params.encoding)?;
+    let require = params
+        .require
+        .iter()
+        .map(|capability| parse(capability))
+        .collect::<Result<Vec<Capability>, _>>()?;
+    let forbid = params
+        .forbid
+        .iter()
+        .map(|capability| parse(capability))
+        .collect::<Result<Vec<Capability>, _>>()?;
+    let report = verify_capabilities(params.value.as_bytes(), encoding, &require, &forbid)
+        .unwrap_or_else(|failure| failure.report);
+    // Unwrap is ok, since the report only consists of strings, booleans and integers
+    Ok(serde_json::to_value(report).unwrap())
+}
+
+fn sign_challenge_string(
+    params: SignChallengeParams,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Result<Value, RpcError> {
+    let algorithm = optional(&params.algorithm, algorithm)?;
+    let key = optional(&params.key, Target::Actor)?;
+    let expires = match params.expires.as_deref() {
+        Some(expires) => parse_timestamp(expires).map_err(RpcError::invalid_params)?,
+        None => unix_now() + CHALLENGE_VALIDITY_SECONDS,
+    };
+    let challenge = with_algorithm!(
+        algorithm,
+        A => sign_challenge::<A>(params.challenge, expires, keys, key)
+    )
+    .map_err(RpcError::failed)?;
+    // Unwrap is ok, since the challenge only consists of strings and integers
+    Ok(serde_json::to_value(challenge).unwrap())
+}
+
+fn sign(
+    params: SignMessageParams,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Result<Value, RpcError> {
+    let algorithm = optional(&params.algorithm, algorithm)?;
+    let key = optional(&params.key, Target::Actor)?;
+    let message = with_algorithm!(algorithm, A => sign_message::<A>(params.message, keys, key))
+        .map_err(RpcError::failed)?;
+    // Unwrap is ok, since the message only consists of strings
+    Ok(serde_json::to_value(message).unwrap())
+}
+
+fn issue(
+    params: IssueParams,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Result<Value, RpcError> {
+    let timestamp = |value: Option<String>| {
+        value
+            .as_deref()
+            .map(parse_timestamp)
+            .transpose()
+            .map_err(RpcError::invalid_params)
+    };
+    let args = IssueArgs {
+        input: Input {
+            value: Some(params.value),
+            input: None,
+        },
+        encoding: parse(&params.encoding)?,
+        output_encoding: optional(&params.output_encoding, Format::Pem)?,
+        target: parse(&params.target)?,
+        serial: params.serial.unwrap_or(1),
+        issuer: params.issuer.unwrap_or_else(|| DEFAULT_ISSUER.to_string()),
+        not_before: timestamp(params.not_before)?,
+        not_after: timestamp(params.not_after)?,
+    };
+    let algorithm = optional(&params.algorithm, algorithm)?;
+    encoded_value(issue_encoded(&args, keys, algorithm).map_err(RpcError::failed)?)
+}
+
+fn create(
+    params: CreateCsrParams,
+    keys: &KeyRing,
+    algorithm: SignatureAlgorithm,
+) -> Result<Value, RpcError> {
+    let algorithm = optional(&params.algorithm, algorithm)?;
+    let target = parse(&params.target)?;
+    let key = optional(&params.key, Target::Actor)?;
+    let encoding = optional(&params.encoding, Format::Pem)?;
+    let csr = with_algorithm!(
+        algorithm,
+        A => create_csr::<A>(&params.subject, target, keys, key, encoding)
+    )
+    .map_err(RpcError::failed)?;
+    encoded_value(csr)
+}
+
+/// Return a built-in key, like the `keys` command prints it
+fn key(params: KeyParams, algorithm: SignatureAlgorithm) -> Result<Value, RpcError> {
+    let algorithm = optional(&params.algorithm, algorithm)?;
+    let choice: KeyChoice = parse(&params.key)?;
+    let pem = with_algorithm!(algorithm, A => A::builtin_key_pem(choice));
+    Ok(json!({ "pem": pem }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use stimmgabel::errors::ExitCode;
+    use stimmgabel::keyring::KeyRing;
+
+    use crate::cli::SignatureAlgorithm;
+
+    use super::{handle_line, INVALID_PARAMS, METHOD_NOT_FOUND, PARSE_ERROR};
+
+    fn handle(line: &str) -> Option<Value> {
+        handle_line(line, &KeyRing::default(), SignatureAlgorithm::Ed25519)
+    }
+
+    #[test]
+    fn signed_message_verifies() {
+        let signed =
+            handle(r#"{"jsonrpc":"2.0","id":1,"method":"sign.message","params":{"message":"Hi"}}"#)
+                .unwrap();
+        assert_eq!(signed["id"], 1);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": "verify",
+            "method": "verify.message",
+            "params": { "value": signed["result"].to_string() },
+        });
+        let verified = handle(&request.to_string()).unwrap();
+        assert_eq!(verified["id"], "verify");
+        assert_eq!(verified["result"]["exit_code"], 0);
+    }
+
+    #[test]
+    fn created_csr_can_be_issued() {
+        let created = handle(
+            r#"{"jsonrpc":"2.0","id":1,"method":"csr.create","params":{"subject":"CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1","target":"actor"}}"#,
+        )
+        .unwrap();
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "issue",
+            "params": {
+                "value": created["result"]["value"],
+                "encoding": "pem",
+                "target": "actor",
+            },
+        });
+        let issued = handle(&request.to_string()).unwrap();
+        assert!(issued["result"]["value"]
+            .as_str()
+            .unwrap()
+            .starts_with("-----BEGIN CERTIFICATE-----"));
+    }
+
+    #[test]
+    fn chain_is_verified_with_inline_trust_anchor() {
+        let created = handle(
+            r#"{"jsonrpc":"2.0","id":1,"method":"csr.create","params":{"subject":"CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1","target":"actor"}}"#,
+        )
+        .unwrap();
+        let issued = handle(
+            &json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "issue",
+                "params": {
+                    "value": created["result"]["value"],
+                    "encoding": "pem",
+                    "target": "actor",
+                },
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let verify = |key: &str| {
+            let pem = handle(&format!(
+                r#"{{"jsonrpc":"2.0","id":3,"method":"keys.get","params":{{"key":"{}"}}}}"#,
+                key
+            ))
+            .unwrap();
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": 4,
+                "method": "verify.chain",
+                "params": {
+                    "values": [issued["result"]["value"]],
+                    "encoding": "pem",
+                    "trust_anchor": pem["result"]["pem"],
+                },
+            });
+            handle(&request.to_string()).unwrap()["result"]["exit_code"].clone()
+        };
+        assert_eq!(verify("homeserver-public"), 0);
+        assert_eq!(verify("actor-public"), ExitCode::BROKEN_CHAIN.bits());
+    }
+
+    #[test]
+    fn errors_follow_json_rpc() {
+        assert_eq!(handle("{").unwrap()["error"]["code"], PARSE_ERROR);
+        let unknown = handle(r#"{"jsonrpc":"2.0","id":1,"method":"unknown"}"#).unwrap();
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        let invalid = handle(r#"{"jsonrpc":"2.0","id":1,"method":"keys.get","params":{}}"#);
+        assert_eq!(invalid.unwrap()["error"]["code"], INVALID_PARAMS);
+        // Notifications are not answered
+        assert_eq!(
+            handle(r#"{"jsonrpc":"2.0","method":"keys.get","params":{"key":"actor-public"}}"#),
+            None
+        );
+    }
+}
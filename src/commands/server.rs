@@ -0,0 +1,511 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use polyproto::der::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use stimmgabel::algorithm::{detect_algorithm, DetectedAlgorithm};
+use stimmgabel::encoding::{decode_der, encode_der, unix_now};
+use stimmgabel::errors::ExitCode;
+use stimmgabel::keyring::KeyRing;
+use stimmgabel::polyproto::challenge::{SignedChallenge, CHALLENGE_VALIDITY_SECONDS};
+use stimmgabel::polyproto::federation_id::FederationId;
+use stimmgabel::polyproto::message::Message;
+use stimmgabel::verify::verify_message;
+use stimmgabel::with_algorithm;
+
+use crate::cli::{Format, Input, IssueArgs, ServerArgs, SignatureAlgorithm, Target};
+use crate::commands::csr::create_csr;
+use crate::commands::issue::issue_encoded;
+
+/// The routes of the polyproto core API served by the reference home server
+pub(crate) const CHALLENGE_ROUTE: &str = "/.p2/core/v1/challenge";
+pub(crate) const SESSION_ID_CERT_ROUTE: &str = "/.p2/core/v1/session/idcert";
+pub(crate) const SERVER_ID_CERT_ROUTE: &str = "/.p2/core/v1/idcert/server";
+pub(crate) const SERVER_PUBLIC_KEY_ROUTE: &str = "/.p2/core/v1/key/server";
+/// Followed by the federation ID of the actor whose public key is looked up
+pub(crate) const ACTOR_PUBLIC_KEY_ROUTE: &str = "/.p2/core/v1/key/actor";
+
+/// The body of a request for an actor ID-Cert: The PEM encoded ID-CSR, along with a challenge
+/// string handed out by the home server, signed with the key the ID-CSR names
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct IdCsrSubmission {
+    pub(crate) csr: String,
+    pub(crate) challenge: SignedChallenge,
+}
+
+/// Everything the routes of the home server share
+pub(crate) struct ServerState {
+    keys: KeyRing,
+    algorithm: SignatureAlgorithm,
+    issuer: String,
+    /// The PEM encoded ID-Cert of the home server
+    id_cert: String,
+    /// The PEM encoded public key of the home server
+    public_key: String,
+    /// Counts the challenge strings handed out. Challenge strings are derived from this count
+    /// instead of being random, so that every run of the home server behaves the same. Anyone can
+    /// predict them, which is why this home server must never be used outside of tests.
+    challenges: AtomicU64,
+    /// The challenge strings which have been handed out, but not answered yet, and when they expire
+    open_challenges: Mutex<HashMap<String, u64>>,
+    /// The PEM encoded public keys of the actors ID-Certs have been issued to, by federation ID
+    actor_keys: Mutex<HashMap<String, String>>,
+    /// The serial number of the last issued certificate
+    serial: AtomicU64,
+}
+
+impl ServerState {
+    /// Issue the home server's own ID-Cert, signed with the issuer key, and set up the state of the
+    /// home server around it
+    pub(crate) fn new(
+        args: &ServerArgs,
+        keys: KeyRing,
+        algorithm: SignatureAlgorithm,
+    ) -> Result<Self, (i32, String)> {
+        let csr = with_algorithm!(
+            algorithm,
+            A => create_csr::<A>(
+                &args.issuer,
+                Target::Homeserver,
+                &keys,
+                Target::Homeserver,
+                Format::Pem,
+            )
+        )?;
+        let issue_args = IssueArgs {
+            input: Input {
+                value: Some(String::from_utf8_lossy(&csr).into_owned()),
+                input: None,
+            },
+            encoding: Format::Pem,
+            output_encoding: Format::Der,
+            target: Target::Homeserver,
+            serial: 1,
+            issuer: args.issuer.clone(),
+            not_before: None,
+            not_after: None,
+        };
+        let der = issue_encoded(&issue_args, &keys, algorithm)?;
+        let invalid = |e: polyproto::der::Error| (ExitCode::INVALID_INPUT.bits(), e.to_string());
+        let public_key = x509_cert::Certificate::from_der(&der)
+            .map_err(invalid)?
+            .tbs_certificate
+            .subject_public_key_info
+            .to_der()
+            .map_err(invalid)?;
+        let pem = |der: &[u8], label: &str| {
+            encode_der(der, label, Format::Pem)
+                .map(|pem| String::from_utf8_lossy(&pem).into_owned())
+                .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e))
+        };
+        Ok(Self {
+            id_cert: pem(&der, "CERTIFICATE")?,
+            public_key: pem(&public_key, "PUBLIC KEY")?,
+            keys,
+            algorithm,
+            issuer: args.issuer.clone(),
+            challenges: AtomicU64::new(0),
+            open_challenges: Mutex::new(HashMap::new()),
+            actor_keys: Mutex::new(HashMap::new()),
+            serial: AtomicU64::new(1),
+        })
+    }
+}
+
+/// Run a reference home server implementing the core routes of the polyproto HTTP API, until the
+/// process is stopped. Returns the exit code to exit with.
+pub(crate) fn server(args: &ServerArgs, keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    let state = match ServerState::new(args, keys.clone(), algorithm) {
+        Ok(state) => Arc::new(state),
+        Err((code, message)) => {
+            eprintln!(
+                "Could not issue the ID-Cert of the home server: {}",
+                message
+            );
+            return code;
+        }
+    };
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Could not start the home server: {}", e);
+            return ExitCode::INVALID_INPUT.bits();
+        }
+    };
+    runtime.block_on(async {
+        let listener = match tokio::net::TcpListener::bind(args.listen).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Could not listen on {}: {}", args.listen, e);
+                return ExitCode::INVALID_INPUT.bits();
+            }
+        };
+        let address = listener.local_addr().unwrap_or(args.listen);
+        eprintln!("Listening on {}", address);
+        match axum::serve(listener, router(state)).await {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::INVALID_INPUT.bits()
+            }
+        }
+    })
+}
+
+pub(crate) fn router(state: Arc<ServerState>) -> Router {
+    Router::new()
+        .route(CHALLENGE_ROUTE, get(challenge))
+        .route(SESSION_ID_CERT_ROUTE, post(session_id_cert))
+        .route(SERVER_ID_CERT_ROUTE, get(server_id_cert))
+        .route(SERVER_PUBLIC_KEY_ROUTE, get(server_public_key))
+        .route(
+            &format!("{}/:federation_id", ACTOR_PUBLIC_KEY_ROUTE),
+            get(actor_public_key),
+        )
+        .with_state(state)
+}
+
+/// Hand out a challenge string, which actors have to sign to prove the possession of their key.
+/// Challenge strings are deliberately predictable, see [ServerState].
+async fn challenge(State(state): State<Arc<ServerState>>) -> Json<serde_json::Value> {
+    let number = state.challenges.fetch_add(1, Ordering::Relaxed);
+    let challenge = format!("stimmgabel-challenge-{:044}", number);
+    let now = unix_now();
+    let expires = now + CHALLENGE_VALIDITY_SECONDS;
+    {
+        // Unwraps are ok, since the locks are never held while panicking
+        let mut open_challenges = state.open_challenges.lock().unwrap();
+        open_challenges.retain(|_, expires| *expires >= now);
+        open_challenges.insert(challenge.clone(), expires);
+    }
+    Json(json!({ "challenge": challenge, "expires": expires }))
+}
+
+/// Issue an actor ID-Cert from the ID-CSR in the request body, once the actor answered one of the
+/// challenge strings handed out with the key the ID-CSR names. Each challenge string can only be
+/// answered once.
+async fn session_id_cert(State(state): State<Arc<ServerState>>, body: String) -> Response {
+    let submission: IdCsrSubmission = match serde_json::from_str(&body) {
+        Ok(submission) => submission,
+        Err(e) => return rejection(StatusCode::BAD_REQUEST, ExitCode::INVALID_INPUT, e),
+    };
+    let open_challenge = state
+        .open_challenges
+        .lock()
+        .unwrap()
+        .remove(&submission.challenge.challenge);
+    if open_challenge != Some(submission.challenge.expires) {
+        return rejection(
+            StatusCode::UNAUTHORIZED,
+            ExitCode::INVALID_INPUT,
+            "The challenge string was not handed out by this home server or was already answered",
+        );
+    }
+    if submission.challenge.expires < unix_now() {
+        return rejection(
+            StatusCode::UNAUTHORIZED,
+            ExitCode::EXPIRED,
+            format!(
+                "The challenge string expired at {}",
+                submission.challenge.expires
+            ),
+        );
+    }
+    if let Err(response) =
+        proof_of_possession(&submission.csr, &submission.challenge, state.algorithm)
+    {
+        return response;
+    }
+    let serial = state.serial.fetch_add(1, Ordering::Relaxed) + 1;
+    let args = IssueArgs {
+        input: Input {
+            value: Some(submission.csr),
+            input: None,
+        },
+        encoding: Format::Pem,
+        output_encoding: Format::Pem,
+        target: Target::Actor,
+        serial,
+        issuer: state.issuer.clone(),
+        not_before: None,
+        not_after: None,
+    };
+    let id_cert = match issue_encoded(&args, &state.keys, state.algorithm) {
+        Ok(id_cert) => id_cert,
+        Err((exit_code, message)) => {
+            return rejection(
+                StatusCode::BAD_REQUEST,
+                ExitCode::from_bits_retain(exit_code),
+                message,
+            )
+        }
+    };
+    if let Some((federation_id, public_key)) = actor_key(&id_cert) {
+        state
+            .actor_keys
+            .lock()
+            .unwrap()
+            .insert(federation_id.to_string(), public_key);
+    }
+    Json(json!({
+        "id_cert": String::from_utf8_lossy(&id_cert),
+        "token": format!("stimmgabel-session-{}", serial),
+    }))
+    .into_response()
+}
+
+/// Check that the challenge string was signed with the key the PEM encoded ID-CSR names, before
+/// anything is issued for it. ID-CSRs are checked with the [SignatureAlgorithm] named in their
+/// algorithm identifiers, or with `algorithm` if these cannot be read.
+fn proof_of_possession(
+    csr: &str,
+    challenge: &SignedChallenge,
+    algorithm: SignatureAlgorithm,
+) -> Result<(), Response> {
+    let der = decode_der(csr.as_bytes(), Format::Pem)
+        .map_err(|e| rejection(StatusCode::BAD_REQUEST, ExitCode::GARBLED_INPUT, e))?;
+    let request = x509_cert::request::CertReq::from_der(&der)
+        .map_err(|e| rejection(StatusCode::BAD_REQUEST, ExitCode::GARBLED_INPUT, e))?;
+    let algorithm = match detect_algorithm(&der) {
+        DetectedAlgorithm::Supported(algorithm) => algorithm,
+        DetectedAlgorithm::Unsupported(e) => {
+            return Err(rejection(
+                StatusCode::BAD_REQUEST,
+                ExitCode::UNSUPPORTED_ALGORITHM,
+                e,
+            ))
+        }
+        DetectedAlgorithm::Unreadable => algorithm,
+    };
+    // A challenge string signed by an actor is a message with the key of the ID-CSR as its sender
+    let message = Message {
+        message: challenge.challenge.clone(),
+        signature: challenge.signature.clone(),
+        public_key: BASE64.encode(request.info.public_key.subject_public_key.raw_bytes()),
+    };
+    // Unwrap is ok, since the message only consists of strings
+    let value = serde_json::to_vec(&message).unwrap();
+    match verify_message(&value, algorithm) {
+        Ok(_) => Ok(()),
+        Err(failure) => Err(rejection(
+            StatusCode::UNAUTHORIZED,
+            failure.exit_code(),
+            failure,
+        )),
+    }
+}
+
+/// The federation ID named by an encoded ID-Cert and its PEM encoded public key, if it names one
+fn actor_key(id_cert: &[u8]) -> Option<(FederationId, String)> {
+    let der = decode_der(id_cert, Format::Pem).ok()?;
+    let certificate = x509_cert::Certificate::from_der(&der).ok()?;
+    let federation_id = FederationId::from_subject(&certificate.tbs_certificate.subject).ok()??;
+    let public_key = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .ok()?;
+    let pem = encode_der(&public_key, "PUBLIC KEY", Format::Pem).ok()?;
+    Some((federation_id, String::from_utf8_lossy(&pem).into_owned()))
+}
+
+/// Answer a request which cannot be fulfilled with the reason and the exit code stimmgabel would
+/// have exited with
+fn rejection(status: StatusCode, exit_code: ExitCode, error: impl ToString) -> Response {
+    (
+        status,
+        Json(json!({ "error": error.to_string(), "exit_code": exit_code.bits() })),
+    )
+        .into_response()
+}
+
+async fn server_id_cert(State(state): State<Arc<ServerState>>) -> Response {
+    pem_response(state.id_cert.clone())
+}
+
+async fn server_public_key(State(state): State<Arc<ServerState>>) -> Response {
+    pem_response(state.public_key.clone())
+}
+
+/// Look up the public key of the last ID-Cert issued to the actor with the given federation ID
+async fn actor_public_key(
+    State(state): State<Arc<ServerState>>,
+    Path(federation_id): Path<String>,
+) -> Response {
+    let public_key = state
+        .actor_keys
+        .lock()
+        .unwrap()
+        .get(&federation_id)
+        .cloned();
+    match public_key {
+        Some(public_key) => pem_response(public_key),
+        None => rejection(
+            StatusCode::NOT_FOUND,
+            ExitCode::INVALID_INPUT,
+            format!("No ID-Cert has been issued to {}", federation_id),
+        ),
+    }
+}
+
+fn pem_response(pem: String) -> Response {
+    ([(header::CONTENT_TYPE, "application/x-pem-file")], pem).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use axum::body::to_bytes;
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::response::Response;
+    use stimmgabel::algorithm::{Algorithm, Ed25519};
+    use stimmgabel::encoding::unix_now;
+    use stimmgabel::keyring::KeyRing;
+    use stimmgabel::options::{Format, KeyChoice, Target};
+    use stimmgabel::verify::verify_id_cert;
+
+    use crate::cli::{ServerArgs, SignatureAlgorithm, DEFAULT_ISSUER};
+    use crate::commands::csr::create_csr;
+    use crate::commands::sign::sign_challenge;
+
+    use super::{actor_public_key, challenge, session_id_cert, IdCsrSubmission, ServerState};
+
+    fn state() -> Arc<ServerState> {
+        let args = ServerArgs {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            issuer: DEFAULT_ISSUER.to_string(),
+        };
+        Arc::new(ServerState::new(&args, KeyRing::default(), SignatureAlgorithm::Ed25519).unwrap())
+    }
+
+    #[test]
+    fn home_server_id_cert_verifies() {
+        let state = state();
+        verify_id_cert(
+            state.id_cert.as_bytes(),
+            Format::Pem,
+            Target::Homeserver,
+            unix_now(),
+            &KeyRing::default(),
+        )
+        .unwrap();
+        assert!(state.public_key.starts_with("-----BEGIN PUBLIC KEY-----"));
+    }
+
+    #[tokio::test]
+    async fn challenges_are_predictable() {
+        let server = state();
+        let first = challenge(State(server.clone())).await;
+        let second = challenge(State(server)).await;
+        assert_ne!(first.0["challenge"], second.0["challenge"]);
+        assert!(first.0["challenge"].as_str().unwrap().len() >= 32);
+        // A restarted server hands out the same challenges again
+        let restarted = challenge(State(state())).await;
+        assert_eq!(first.0["challenge"], restarted.0["challenge"]);
+    }
+
+    /// Answer a challenge string of the home server and submit an ID-CSR along with it
+    async fn submission(server: &Arc<ServerState>) -> String {
+        let challenge = challenge(State(server.clone())).await.0;
+        let challenge = sign_challenge::<Ed25519>(
+            challenge["challenge"].as_str().unwrap().to_string(),
+            challenge["expires"].as_u64().unwrap(),
+            &KeyRing::default(),
+            Target::Actor,
+        )
+        .unwrap();
+        let csr = create_csr::<Ed25519>(
+            "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+            Target::Actor,
+            &KeyRing::default(),
+            Target::Actor,
+            Format::Pem,
+        )
+        .unwrap();
+        serde_json::to_string(&IdCsrSubmission {
+            csr: String::from_utf8(csr).unwrap(),
+            challenge,
+        })
+        .unwrap()
+    }
+
+    async fn body(response: Response) -> String {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn session_id_cert_is_issued_from_csr() {
+        let server = state();
+        let submission = submission(&server).await;
+        let response = session_id_cert(State(server.clone()), submission.clone()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body(response).await).unwrap();
+        let id_cert = body["id_cert"].as_str().unwrap();
+        verify_id_cert(
+            id_cert.as_bytes(),
+            Format::Pem,
+            Target::Actor,
+            unix_now(),
+            &KeyRing::default(),
+        )
+        .unwrap();
+
+        // Challenge strings can only be answered once
+        let replayed = session_id_cert(State(server.clone()), submission).await;
+        assert_eq!(replayed.status(), StatusCode::UNAUTHORIZED);
+        let garbled = session_id_cert(State(server), "not a csr".to_string()).await;
+        assert_eq!(garbled.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn challenge_has_to_be_signed_with_the_key_of_the_csr() {
+        let server = state();
+        let mut submission: IdCsrSubmission =
+            serde_json::from_str(&submission(&server).await).unwrap();
+        submission.challenge = sign_challenge::<Ed25519>(
+            submission.challenge.challenge,
+            submission.challenge.expires,
+            &KeyRing::default(),
+            Target::Homeserver,
+        )
+        .unwrap();
+        let submission = serde_json::to_string(&submission).unwrap();
+        let response = session_id_cert(State(server.clone()), submission).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        // Nothing is issued without a proof of possession
+        assert_eq!(server.serial.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn actor_public_key_is_looked_up_by_federation_id() {
+        let server = state();
+        let federation_id = "flori@polyphony.chat".to_string();
+        let unknown = actor_public_key(State(server.clone()), Path(federation_id.clone())).await;
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+        let submission = submission(&server).await;
+        session_id_cert(State(server.clone()), submission).await;
+        let response = actor_public_key(State(server), Path(federation_id)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body(response).await.trim_end(),
+            Ed25519::builtin_key_pem(KeyChoice::ActorPublic)
+        );
+    }
+}
@@ -0,0 +1,108 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use polyproto::key::{PrivateKey, PublicKey};
+use polyproto::spki::SignatureBitStringEncoding;
+use serde::Serialize;
+use stimmgabel::algorithm::Algorithm;
+use stimmgabel::encoding::unix_now;
+use stimmgabel::errors::ExitCode;
+use stimmgabel::keyring::KeyRing;
+use stimmgabel::polyproto::challenge::{SignedChallenge, CHALLENGE_VALIDITY_SECONDS};
+use stimmgabel::polyproto::message::Message;
+use stimmgabel::with_algorithm;
+
+use crate::cli::{SignMode, SignatureAlgorithm, Target};
+
+/// Produce the signed polyproto value described by the given [SignMode] and print it to stdout.
+/// Values are signed with the given [SignatureAlgorithm]. Returns the exit code to exit with.
+pub(crate) fn sign(mode: SignMode, keys: &KeyRing, algorithm: SignatureAlgorithm) -> i32 {
+    match mode {
+        SignMode::Message { message, key } => {
+            print_json(with_algorithm!(algorithm, A => sign_message::<A>(message, keys, key)))
+        }
+        SignMode::Challenge {
+            challenge,
+            expires,
+            key,
+        } => {
+            let expires = expires.unwrap_or_else(|| unix_now() + CHALLENGE_VALIDITY_SECONDS);
+            print_json(with_algorithm!(
+                algorithm,
+                A => sign_challenge::<A>(challenge, expires, keys, key)
+            ))
+        }
+    }
+}
+
+/// Print a signed value as JSON, or the reason why it could not be signed. Returns the exit code to
+/// exit with.
+fn print_json<T: Serialize>(signed: Result<T, (i32, String)>) -> i32 {
+    match signed {
+        Ok(signed) => {
+            // Unwrap is ok, since signed values only consist of strings and integers
+            println!("{}", serde_json::to_string(&signed).unwrap());
+            0
+        }
+        Err((code, message)) => {
+            eprintln!("{}", message);
+            code
+        }
+    }
+}
+
+/// Sign `message`, producing a [Message] in the format expected by `verify message`.
+pub(crate) fn sign_message<A: Algorithm>(
+    message: String,
+    keys: &KeyRing,
+    key: Target,
+) -> Result<Message, (i32, String)> {
+    let private_key = keys
+        .signing_key::<A>(key)
+        .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e))?;
+    Ok(Message {
+        signature: signature::<A>(&private_key, message.as_bytes())?,
+        public_key: BASE64.encode(
+            private_key
+                .pubkey()
+                .public_key_info()
+                .public_key_bitstring
+                .raw_bytes(),
+        ),
+        message,
+    })
+}
+
+/// Sign `challenge`, producing a [SignedChallenge] in the format expected by `verify challenge`.
+pub(crate) fn sign_challenge<A: Algorithm>(
+    challenge: String,
+    expires: u64,
+    keys: &KeyRing,
+    key: Target,
+) -> Result<SignedChallenge, (i32, String)> {
+    let private_key = keys
+        .signing_key::<A>(key)
+        .map_err(|e| (ExitCode::INVALID_INPUT.bits(), e))?;
+    Ok(SignedChallenge {
+        signature: signature::<A>(&private_key, challenge.as_bytes())?,
+        challenge,
+        expires,
+    })
+}
+
+/// Sign `data` and return the base64 encoded signature
+fn signature<A: Algorithm>(
+    private_key: &A::PrivateKey,
+    data: &[u8],
+) -> Result<String, (i32, String)> {
+    let signature = private_key.sign(data).to_bitstring().map_err(|e| {
+        (
+            ExitCode::INVALID_INPUT.bits(),
+            format!("Could not encode the signature: {}", e),
+        )
+    })?;
+    Ok(BASE64.encode(signature.raw_bytes()))
+}
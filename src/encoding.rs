@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use polyproto::der::asn1::{GeneralizedTime, UtcTime};
+use polyproto::der::pem::LineEnding;
+use x509_cert::time::Time;
+
+use crate::options::Format;
+
+/// Turn a value supplied in any of the supported [Format]s into raw DER bytes. Whitespace inside of
+/// base64 and hex encoded values is ignored, so that line-wrapped values can be passed in as-is.
+pub fn decode_der(value: &[u8], encoding: Format) -> Result<Vec<u8>, String> {
+    match encoding {
+        Format::Der => Ok(value.to_vec()),
+        Format::DerBase64 => BASE64
+            .decode(strip_whitespace(value))
+            .map_err(|e| format!("Value is not valid base64: {}", e)),
+        Format::DerHex => hex::decode(strip_whitespace(value))
+            .map_err(|e| format!("Value is not valid hex: {}", e)),
+        Format::Pem => match polyproto::der::pem::decode_vec(value) {
+            Ok((_label, der)) => Ok(der),
+            Err(e) => Err(format!("Value is not valid PEM: {}", e)),
+        },
+    }
+}
+
+/// Encode the given DER bytes in the given [Format]. `label` is the PEM type label used when encoding
+/// as PEM, e.g. `CERTIFICATE`. The returned bytes are ready to be written to stdout.
+pub fn encode_der(der: &[u8], label: &str, encoding: Format) -> Result<Vec<u8>, String> {
+    match encoding {
+        Format::Der => Ok(der.to_vec()),
+        Format::DerBase64 => Ok(BASE64.encode(der).into_bytes()),
+        Format::DerHex => Ok(hex::encode(der).into_bytes()),
+        Format::Pem => polyproto::der::pem::encode_string(label, LineEnding::LF, der)
+            .map(String::into_bytes)
+            .map_err(|e| format!("Could not encode value as PEM: {}", e)),
+    }
+}
+
+/// Convert seconds since the UNIX epoch into an X.509 [Time]. As required by RFC 5280, UTCTime is
+/// used for dates before 2050 and GeneralizedTime for all later dates.
+pub fn x509_time(unix_seconds: u64) -> polyproto::der::Result<Time> {
+    let duration = Duration::from_secs(unix_seconds);
+    match UtcTime::from_unix_duration(duration) {
+        Ok(time) => Ok(Time::UtcTime(time)),
+        Err(_) => Ok(Time::GeneralTime(GeneralizedTime::from_unix_duration(
+            duration,
+        )?)),
+    }
+}
+
+/// The current time in seconds since the UNIX epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Decode a base64 encoded value, ignoring any whitespace in it
+pub fn decode_base64(value: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    BASE64.decode(strip_whitespace(value.as_bytes()))
+}
+
+fn strip_whitespace(value: &[u8]) -> Vec<u8> {
+    value
+        .iter()
+        .copied()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect()
+}
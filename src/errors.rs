@@ -3,13 +3,52 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use bitflags::bitflags;
+use polyproto::errors::composite::{ConversionError, PublicKeyError};
+
+use crate::polyproto::federation_id::FederationIdError;
 
 bitflags! {
+    /// The bits of the exit code stimmgabel exits with. Each bit stands for a kind of check that
+    /// failed.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct ExitCode: i32 {
         const GARBLED_INPUT = 1 << 0;
         const INVALID_INPUT = 1 << 1;
         const CONSTRAINT_VIOLATION = 1 << 2;
         const BAD_SIGNATURE = 1 << 3;
         const BAD_PUBLIC_KEY = 1 << 4;
+        const BROKEN_CHAIN = 1 << 5;
+        const EXPIRED = 1 << 6;
+        const NOT_YET_VALID = 1 << 7;
+        const UNSUPPORTED_ALGORITHM = 1 << 8;
+        const FEDERATION_ID_MISMATCH = 1 << 9;
+    }
+}
+
+/// Map a [ConversionError] to the [ExitCode] bit best describing it.
+pub fn conversion_error_to_exit_code(error: &ConversionError) -> i32 {
+    match error {
+        ConversionError::ConstraintError(_) => ExitCode::CONSTRAINT_VIOLATION.bits(),
+        ConversionError::InvalidInput(_)
+        | ConversionError::UnknownCriticalExtension { .. }
+        | ConversionError::ConstOidError(_) => ExitCode::INVALID_INPUT.bits(),
+        ConversionError::DerError(_) => ExitCode::GARBLED_INPUT.bits(),
+        ConversionError::IdCertError(idcert_error) => public_key_error_to_exit_code(idcert_error),
+    }
+}
+
+/// Map a [PublicKeyError] to the [ExitCode] bit best describing it.
+pub fn public_key_error_to_exit_code(error: &PublicKeyError) -> i32 {
+    match error {
+        PublicKeyError::BadSignature => ExitCode::BAD_SIGNATURE.bits(),
+        PublicKeyError::BadPublicKeyInfo => ExitCode::BAD_PUBLIC_KEY.bits(),
+    }
+}
+
+/// Map a [FederationIdError] to the [ExitCode] bit best describing it.
+pub fn federation_id_error_to_exit_code(error: &FederationIdError) -> i32 {
+    match error {
+        FederationIdError::Malformed(_) => ExitCode::CONSTRAINT_VIOLATION.bits(),
+        FederationIdError::DomainMismatch { .. } => ExitCode::FEDERATION_ID_MISMATCH.bits(),
     }
 }
@@ -0,0 +1,380 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! A C ABI for running the verification in-process from polyproto implementations not written in
+//! Rust. The functions in this module wrap those of [crate::verify] and are exported by the shared
+//! library built from this crate. `include/stimmgabel.h` declares them for C and is generated from
+//! this module with cbindgen.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use crate::encoding::unix_now;
+use crate::errors::ExitCode;
+use crate::keyring::KeyRing;
+use crate::options::{Format, SignatureAlgorithm, Target};
+use crate::report::Report;
+use crate::verify::{verify_id_cert, verify_id_csr, verify_message};
+
+/// Raw, binary DER
+pub const STIMMGABEL_FORMAT_DER: u32 = 0;
+/// Base64 encoded DER
+pub const STIMMGABEL_FORMAT_DER_BASE64: u32 = 1;
+/// Hex encoded DER
+pub const STIMMGABEL_FORMAT_DER_HEX: u32 = 2;
+pub const STIMMGABEL_FORMAT_PEM: u32 = 3;
+
+pub const STIMMGABEL_TARGET_ACTOR: u32 = 0;
+pub const STIMMGABEL_TARGET_HOMESERVER: u32 = 1;
+
+pub const STIMMGABEL_ALGORITHM_ED25519: u32 = 0;
+pub const STIMMGABEL_ALGORITHM_ED448: u32 = 1;
+pub const STIMMGABEL_ALGORITHM_ECDSA_P256: u32 = 2;
+pub const STIMMGABEL_ALGORITHM_ECDSA_P384: u32 = 3;
+pub const STIMMGABEL_ALGORITHM_ML_DSA_44: u32 = 4;
+pub const STIMMGABEL_ALGORITHM_ML_DSA_65: u32 = 5;
+
+/// Returned by the verification functions if stimmgabel panicked during the verification, which
+/// is a bug in stimmgabel. No report is handed out in this case.
+pub const STIMMGABEL_PANIC: i32 = -1;
+
+/// Load the keys to verify with from the given files, which are read like the arguments of
+/// `--actor-key` and `--issuer-key`. Either path may be NULL, in which case the built-in key is
+/// used. Returns NULL if a file cannot be loaded or stimmgabel panicked. The returned key ring has
+/// to be freed with [stimmgabel_keyring_free].
+///
+/// # Safety
+///
+/// `actor_key` and `issuer_key` have to be NULL or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn stimmgabel_keyring_load(
+    actor_key: *const c_char,
+    issuer_key: *const c_char,
+) -> *mut KeyRing {
+    guard(std::ptr::null_mut(), || {
+        let (actor_key, issuer_key) = match (path(actor_key), path(issuer_key)) {
+            (Ok(actor_key), Ok(issuer_key)) => (actor_key, issuer_key),
+            (Err(e), _) | (_, Err(e)) => {
+                log::error!("{}", e);
+                return std::ptr::null_mut();
+            }
+        };
+        match KeyRing::load(actor_key, issuer_key) {
+            Ok(keys) => Box::into_raw(Box::new(keys)),
+            Err(e) => {
+                log::error!("{}", e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Free a key ring returned by [stimmgabel_keyring_load].
+///
+/// # Safety
+///
+/// `keys` has to be NULL or a key ring returned by [stimmgabel_keyring_load], which has not been
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn stimmgabel_keyring_free(keys: *mut KeyRing) {
+    guard((), || {
+        if !keys.is_null() {
+            drop(Box::from_raw(keys));
+        }
+    })
+}
+
+/// Verify an encoded ID-Cert like `stimmgabel verify id-cert` does. `at` is the time at which the
+/// certificate has to be valid in seconds since the UNIX epoch, or 0 for now. `keys` may be NULL to
+/// verify with the built-in keys. Returns the exit code of the verification. If `report` is not
+/// NULL, it is set to the JSON encoded verification report, which has to be freed with
+/// [stimmgabel_report_free]. If stimmgabel panicked, [STIMMGABEL_PANIC] is returned and `report`
+/// is set to NULL.
+///
+/// # Safety
+///
+/// `value` has to point to `value_len` readable bytes, `keys` has to be NULL or a valid key ring and
+/// `report` has to be NULL or point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn stimmgabel_verify_id_cert(
+    value: *const u8,
+    value_len: usize,
+    encoding: u32,
+    target: u32,
+    at: u64,
+    keys: *const KeyRing,
+    report: *mut *mut c_char,
+) -> i32 {
+    verify_guarded(report, || {
+        bytes("id-cert", value, value_len).and_then(|value| {
+            let encoding = parse_format("id-cert", encoding)?;
+            let target = parse_target("id-cert", target)?;
+            let at = if at == 0 { unix_now() } else { at };
+            with_keys(keys, |keys| {
+                verify_id_cert(value, encoding, target, at, keys)
+            })
+        })
+    })
+}
+
+/// Verify an encoded ID-CSR like `stimmgabel verify id-csr` does. See [stimmgabel_verify_id_cert]
+/// for the meaning of the arguments and the return value.
+///
+/// # Safety
+///
+/// `value` has to point to `value_len` readable bytes, `keys` has to be NULL or a valid key ring and
+/// `report` has to be NULL or point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn stimmgabel_verify_id_csr(
+    value: *const u8,
+    value_len: usize,
+    encoding: u32,
+    target: u32,
+    keys: *const KeyRing,
+    report: *mut *mut c_char,
+) -> i32 {
+    verify_guarded(report, || {
+        bytes("id-csr", value, value_len).and_then(|value| {
+            let encoding = parse_format("id-csr", encoding)?;
+            let target = parse_target("id-csr", target)?;
+            with_keys(keys, |keys| verify_id_csr(value, encoding, target, keys))
+        })
+    })
+}
+
+/// Verify a JSON encoded message like `stimmgabel verify message` does. The message has to be signed
+/// with `algorithm`. See [stimmgabel_verify_id_cert] for the meaning of `report` and the return
+/// value.
+///
+/// # Safety
+///
+/// `value` has to point to `value_len` readable bytes and `report` has to be NULL or point to
+/// writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn stimmgabel_verify_message(
+    value: *const u8,
+    value_len: usize,
+    algorithm: u32,
+    report: *mut *mut c_char,
+) -> i32 {
+    verify_guarded(report, || {
+        bytes("message", value, value_len).and_then(|value| {
+            let algorithm = parse_algorithm("message", algorithm)?;
+            verify_message(value, algorithm).map_err(|failure| failure.report)
+        })
+    })
+}
+
+/// Free a report returned by one of the verification functions.
+///
+/// # Safety
+///
+/// `report` has to be NULL or a report returned by one of the verification functions, which has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn stimmgabel_report_free(report: *mut c_char) {
+    guard((), || {
+        if !report.is_null() {
+            drop(CString::from_raw(report));
+        }
+    })
+}
+
+/// Run the function body `body`, returning `fallback` instead of unwinding into the caller, which
+/// is undefined behavior, if it panics
+fn guard<T>(fallback: T, body: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|_| {
+        log::error!("stimmgabel panicked, this is a bug");
+        fallback
+    })
+}
+
+/// Run the verification `verify` and hand its report to the caller. If it panics, `report` is set
+/// to NULL and [STIMMGABEL_PANIC] is returned.
+unsafe fn verify_guarded(
+    report: *mut *mut c_char,
+    verify: impl FnOnce() -> Result<Report, Report>,
+) -> i32 {
+    let exit_code = guard(None, || Some(finish(verify(), report)));
+    exit_code.unwrap_or_else(|| {
+        if !report.is_null() {
+            *report = std::ptr::null_mut();
+        }
+        STIMMGABEL_PANIC
+    })
+}
+
+/// Hand the report to the caller, if they asked for it, and return the exit code
+unsafe fn finish(result: Result<Report, Report>, report: *mut *mut c_char) -> i32 {
+    let result = result.unwrap_or_else(|failed| failed);
+    if !report.is_null() {
+        // Unwraps are ok, since the report only consists of strings, booleans and integers and JSON
+        // escapes NUL characters in strings
+        let json = CString::new(serde_json::to_string(&result).unwrap()).unwrap();
+        *report = json.into_raw();
+    }
+    result.exit_code
+}
+
+/// Run `verify` with the given key ring or the built-in keys, if it is NULL
+unsafe fn with_keys(
+    keys: *const KeyRing,
+    verify: impl FnOnce(&KeyRing) -> Result<Report, crate::verify::VerificationFailure>,
+) -> Result<Report, Report> {
+    match keys.as_ref() {
+        Some(keys) => verify(keys),
+        None => verify(&KeyRing::default()),
+    }
+    .map_err(|failure| failure.report)
+}
+
+unsafe fn bytes<'a>(mode: &str, value: *const u8, value_len: usize) -> Result<&'a [u8], Report> {
+    if !value.is_null() {
+        Ok(std::slice::from_raw_parts(value, value_len))
+    } else if value_len == 0 {
+        Ok(&[])
+    } else {
+        Err(invalid_argument(mode, "The value is NULL"))
+    }
+}
+
+unsafe fn path<'a>(path: *const c_char) -> Result<Option<&'a Path>, String> {
+    if path.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(path)
+        .to_str()
+        .map(|path| Some(Path::new(path)))
+        .map_err(|_| "Key file paths have to be valid UTF-8".to_string())
+}
+
+fn parse_format(mode: &str, encoding: u32) -> Result<Format, Report> {
+    match encoding {
+        STIMMGABEL_FORMAT_DER => Ok(Format::Der),
+        STIMMGABEL_FORMAT_DER_BASE64 => Ok(Format::DerBase64),
+        STIMMGABEL_FORMAT_DER_HEX => Ok(Format::DerHex),
+        STIMMGABEL_FORMAT_PEM => Ok(Format::Pem),
+        _ => Err(invalid_argument(
+            mode,
+            format!("Unknown encoding {}", encoding),
+        )),
+    }
+}
+
+fn parse_target(mode: &str, target: u32) -> Result<Target, Report> {
+    match target {
+        STIMMGABEL_TARGET_ACTOR => Ok(Target::Actor),
+        STIMMGABEL_TARGET_HOMESERVER => Ok(Target::Homeserver),
+        _ => Err(invalid_argument(mode, format!("Unknown target {}", target))),
+    }
+}
+
+fn parse_algorithm(mode: &str, algorithm: u32) -> Result<SignatureAlgorithm, Report> {
+    match algorithm {
+        STIMMGABEL_ALGORITHM_ED25519 => Ok(SignatureAlgorithm::Ed25519),
+        STIMMGABEL_ALGORITHM_ED448 => Ok(SignatureAlgorithm::Ed448),
+        STIMMGABEL_ALGORITHM_ECDSA_P256 => Ok(SignatureAlgorithm::EcdsaP256),
+        STIMMGABEL_ALGORITHM_ECDSA_P384 => Ok(SignatureAlgorithm::EcdsaP384),
+        STIMMGABEL_ALGORITHM_ML_DSA_44 => Ok(SignatureAlgorithm::MlDsa44),
+        STIMMGABEL_ALGORITHM_ML_DSA_65 => Ok(SignatureAlgorithm::MlDsa65),
+        _ => Err(invalid_argument(
+            mode,
+            format!("Unknown signature algorithm {}", algorithm),
+        )),
+    }
+}
+
+fn invalid_argument(mode: &str, error: impl std::fmt::Display) -> Report {
+    Report::new(mode).fail("arguments", ExitCode::INVALID_INPUT.bits(), error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_char, CStr};
+
+    use crate::errors::ExitCode;
+    use crate::report::Report;
+
+    use super::{
+        stimmgabel_report_free, stimmgabel_verify_id_cert, stimmgabel_verify_message,
+        verify_guarded, STIMMGABEL_ALGORITHM_ED25519, STIMMGABEL_FORMAT_DER, STIMMGABEL_PANIC,
+        STIMMGABEL_TARGET_ACTOR,
+    };
+
+    /// Take the report handed out by the C ABI and free it
+    fn take_report(report: *mut c_char) -> serde_json::Value {
+        let json = unsafe { CStr::from_ptr(report) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { stimmgabel_report_free(report) };
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn garbled_certificate_is_rejected_with_report() {
+        let value = b"not a certificate";
+        let mut report = std::ptr::null_mut();
+        let exit_code = unsafe {
+            stimmgabel_verify_id_cert(
+                value.as_ptr(),
+                value.len(),
+                STIMMGABEL_FORMAT_DER,
+                STIMMGABEL_TARGET_ACTOR,
+                0,
+                std::ptr::null(),
+                &mut report,
+            )
+        };
+        assert_ne!(exit_code, 0);
+        let report = take_report(report);
+        assert_eq!(report["mode"], "id-cert");
+        assert_eq!(report["exit_code"], exit_code);
+    }
+
+    #[test]
+    fn unknown_arguments_are_invalid_input() {
+        let exit_code = unsafe {
+            stimmgabel_verify_id_cert(
+                std::ptr::null(),
+                0,
+                42,
+                STIMMGABEL_TARGET_ACTOR,
+                0,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+            )
+        };
+        assert_eq!(exit_code, ExitCode::INVALID_INPUT.bits());
+    }
+
+    #[test]
+    fn message_matches_library_verification() {
+        let value = br#"{"message":"hi","signature":"AAAA","public_key":"AAAA"}"#;
+        let mut report = std::ptr::null_mut();
+        let exit_code = unsafe {
+            stimmgabel_verify_message(
+                value.as_ptr(),
+                value.len(),
+                STIMMGABEL_ALGORITHM_ED25519,
+                &mut report,
+            )
+        };
+        let expected: Report =
+            crate::verify::verify_message(value, crate::options::SignatureAlgorithm::Ed25519)
+                .unwrap_err()
+                .report;
+        assert_eq!(exit_code, expected.exit_code);
+        assert_eq!(take_report(report), serde_json::to_value(expected).unwrap());
+    }
+
+    #[test]
+    fn panic_is_reported_without_report() {
+        let mut report = std::ptr::NonNull::<c_char>::dangling().as_ptr();
+        let exit_code = unsafe { verify_guarded(&mut report, || panic!("verification panicked")) };
+        assert_eq!(exit_code, STIMMGABEL_PANIC);
+        assert!(report.is_null());
+    }
+}
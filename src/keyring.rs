@@ -0,0 +1,127 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::path::Path;
+
+use ed25519_dalek::pkcs8::PrivateKeyInfo;
+use polyproto::key::PrivateKey;
+
+use crate::algorithm::Algorithm;
+use crate::options::Target;
+
+/// The keys used for signing and verifying values. These are the built-in test keys of the
+/// algorithm in use, unless the user supplied their own with `--actor-key` or `--issuer-key`.
+#[derive(Debug, Clone, Default)]
+pub struct KeyRing {
+    actor: Option<KeyFile>,
+    issuer: Option<KeyFile>,
+}
+
+impl KeyRing {
+    /// Load the key ring, replacing the built-in actor and issuer keys with the keys stored in the
+    /// given files, if any.
+    pub fn load(actor_key: Option<&Path>, issuer_key: Option<&Path>) -> Result<Self, String> {
+        Ok(Self {
+            actor: actor_key.map(KeyFile::load).transpose()?,
+            issuer: issuer_key.map(KeyFile::load).transpose()?,
+        })
+    }
+
+    /// The public key of the given [Target]. The key of the home server is the issuer key.
+    pub fn verifying_key<A: Algorithm>(&self, target: Target) -> Result<A::PublicKey, String> {
+        match self.key_file(target) {
+            Some(key_file) => key_file.verifying_key::<A>(),
+            None => Ok(A::builtin_public_key(target)),
+        }
+    }
+
+    /// The private key of the given [Target], which is only available if the built-in key is used
+    /// or the user supplied a private key.
+    pub fn signing_key<A: Algorithm>(&self, target: Target) -> Result<A::PrivateKey, String> {
+        match self.key_file(target) {
+            Some(key_file) => key_file.signing_key::<A>(),
+            None => Ok(A::builtin_private_key(target)),
+        }
+    }
+
+    /// Replace the issuer key with the given one
+    pub fn with_issuer_key(mut self, key: KeyFile) -> Self {
+        self.issuer = Some(key);
+        self
+    }
+
+    fn key_file(&self, target: Target) -> Option<&KeyFile> {
+        match target {
+            Target::Actor => self.actor.as_ref(),
+            Target::Homeserver => self.issuer.as_ref(),
+        }
+    }
+}
+
+/// A key loaded from a file. The file may contain a PKCS#8 private key or an SPKI public key, each
+/// of which may be PEM or DER encoded. Which algorithm the key is for is only checked once the key
+/// is used.
+#[derive(Debug, Clone)]
+pub struct KeyFile {
+    /// Where the key was loaded from, e.g. the path of the file
+    source: String,
+    der: Vec<u8>,
+    private: bool,
+}
+
+impl KeyFile {
+    /// Load the key stored in the given file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read(path)
+            .map_err(|e| format!("Could not read key file {}: {}", path.display(), e))?;
+        Self::parse(contents, &path.display().to_string())
+    }
+
+    /// Parse the contents of a key file, which were loaded from `source`
+    pub fn parse(contents: Vec<u8>, source: &str) -> Result<Self, String> {
+        let (der, private) = match polyproto::der::pem::decode_vec(&contents) {
+            Ok(("PRIVATE KEY", der)) => (der, true),
+            Ok(("PUBLIC KEY", der)) => (der, false),
+            Ok((label, _)) => {
+                return Err(format!(
+                    "Could not parse key file {}: Unsupported PEM label \"{}\"",
+                    source, label
+                ))
+            }
+            // DER does not tell us what kind of key it contains, so we have to look inside
+            Err(_) => {
+                let private = PrivateKeyInfo::try_from(contents.as_slice()).is_ok();
+                (contents, private)
+            }
+        };
+        Ok(Self {
+            source: source.to_string(),
+            der,
+            private,
+        })
+    }
+
+    /// The public key stored in the file. If the file contains a private key, the public key
+    /// belonging to it is returned.
+    pub fn verifying_key<A: Algorithm>(&self) -> Result<A::PublicKey, String> {
+        let key = if self.private {
+            A::private_key_from_der(&self.der).map(|key| key.pubkey().clone())
+        } else {
+            A::public_key_from_der(&self.der)
+        };
+        key.map_err(|e| format!("Could not parse key file {}: {}", self.source, e))
+    }
+
+    /// The private key stored in the file
+    pub fn signing_key<A: Algorithm>(&self) -> Result<A::PrivateKey, String> {
+        if !self.private {
+            return Err(format!(
+                "The key file {} contains a public key, which cannot be used for signing",
+                self.source
+            ));
+        }
+        A::private_key_from_der(&self.der)
+            .map_err(|e| format!("Could not parse key file {}: {}", self.source, e))
+    }
+}
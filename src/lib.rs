@@ -2,6 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod algorithm;
+pub(crate) mod builtin_keys;
+pub mod encoding;
 pub mod errors;
+pub mod ffi;
+pub mod keyring;
+pub mod options;
 pub mod polyproto;
+pub mod report;
+pub mod verify;
 pub use polyproto::{keys, signature};
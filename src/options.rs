@@ -0,0 +1,220 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Options shared by the command line interface and the library API. Each of them can be parsed
+//! from a command line argument.
+
+use std::fmt::Display;
+
+use clap::builder::PossibleValue;
+use clap::ValueEnum;
+
+/// The different keys that can be printed
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum KeyChoice {
+    ActorPrivate,
+    ActorPublic,
+    HomeserverPrivate,
+    HomeserverPublic,
+}
+
+impl ValueEnum for KeyChoice {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            KeyChoice::ActorPrivate,
+            KeyChoice::ActorPublic,
+            KeyChoice::HomeserverPrivate,
+            KeyChoice::HomeserverPublic,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            KeyChoice::ActorPrivate => Some(PossibleValue::new("actor-private")),
+            KeyChoice::ActorPublic => Some(PossibleValue::new("actor-public")),
+            KeyChoice::HomeserverPrivate => Some(PossibleValue::new("homeserver-private")),
+            KeyChoice::HomeserverPublic => Some(PossibleValue::new("homeserver-public")),
+        }
+    }
+}
+
+/// The signature algorithms stimmgabel implements polyproto for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    Ed25519,
+    Ed448,
+    EcdsaP256,
+    EcdsaP384,
+    MlDsa44,
+    MlDsa65,
+}
+
+impl ValueEnum for SignatureAlgorithm {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            SignatureAlgorithm::Ed25519,
+            SignatureAlgorithm::Ed448,
+            SignatureAlgorithm::EcdsaP256,
+            SignatureAlgorithm::EcdsaP384,
+            SignatureAlgorithm::MlDsa44,
+            SignatureAlgorithm::MlDsa65,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            SignatureAlgorithm::Ed25519 => Some(PossibleValue::new("ed25519")),
+            SignatureAlgorithm::Ed448 => Some(PossibleValue::new("ed448")),
+            SignatureAlgorithm::EcdsaP256 => Some(PossibleValue::new("ecdsa-p256")),
+            SignatureAlgorithm::EcdsaP384 => Some(PossibleValue::new("ecdsa-p384")),
+            SignatureAlgorithm::MlDsa44 => Some(PossibleValue::new("ml-dsa-44")),
+            SignatureAlgorithm::MlDsa65 => Some(PossibleValue::new("ml-dsa-65")),
+        }
+    }
+}
+
+impl Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureAlgorithm::Ed25519 => write!(f, "ed25519"),
+            SignatureAlgorithm::Ed448 => write!(f, "ed448"),
+            SignatureAlgorithm::EcdsaP256 => write!(f, "ecdsa-p256"),
+            SignatureAlgorithm::EcdsaP384 => write!(f, "ecdsa-p384"),
+            SignatureAlgorithm::MlDsa44 => write!(f, "ml-dsa-44"),
+            SignatureAlgorithm::MlDsa65 => write!(f, "ml-dsa-65"),
+        }
+    }
+}
+
+/// The encodings ID-Certs and ID-CSRs can be supplied in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Raw, binary DER. Usually supplied via a file or stdin
+    Der,
+    /// Base64 encoded DER
+    DerBase64,
+    /// Hex encoded DER
+    DerHex,
+    Pem,
+}
+
+impl ValueEnum for Format {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Format::Der, Format::DerBase64, Format::DerHex, Format::Pem]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            Format::Der => Some(PossibleValue::new("der")),
+            Format::DerBase64 => Some(PossibleValue::new("der-base64")),
+            Format::DerHex => Some(PossibleValue::new("der-hex")),
+            Format::Pem => Some(PossibleValue::new("pem")),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Der => write!(f, "der"),
+            Format::DerBase64 => write!(f, "der-base64"),
+            Format::DerHex => write!(f, "der-hex"),
+            Format::Pem => write!(f, "pem"),
+        }
+    }
+}
+
+/// Who a value is for: An actor or a home server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Actor,
+    Homeserver,
+}
+
+impl ValueEnum for Target {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Target::Actor, Target::Homeserver]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            Target::Actor => Some(PossibleValue::new("actor")),
+            Target::Homeserver => Some(PossibleValue::new("homeserver")),
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Actor => write!(f, "actor"),
+            Target::Homeserver => write!(f, "homeserver"),
+        }
+    }
+}
+
+/// A capability an ID-Cert can grant: One of the key usages, or acting as a CA as granted by the
+/// basic constraints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    DigitalSignature,
+    ContentCommitment,
+    KeyEncipherment,
+    DataEncipherment,
+    KeyAgreement,
+    KeyCertSign,
+    CrlSign,
+    EncipherOnly,
+    DecipherOnly,
+    Ca,
+}
+
+impl ValueEnum for Capability {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Capability::DigitalSignature,
+            Capability::ContentCommitment,
+            Capability::KeyEncipherment,
+            Capability::DataEncipherment,
+            Capability::KeyAgreement,
+            Capability::KeyCertSign,
+            Capability::CrlSign,
+            Capability::EncipherOnly,
+            Capability::DecipherOnly,
+            Capability::Ca,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            Capability::DigitalSignature => Some(PossibleValue::new("digital-signature")),
+            Capability::ContentCommitment => Some(PossibleValue::new("content-commitment")),
+            Capability::KeyEncipherment => Some(PossibleValue::new("key-encipherment")),
+            Capability::DataEncipherment => Some(PossibleValue::new("data-encipherment")),
+            Capability::KeyAgreement => Some(PossibleValue::new("key-agreement")),
+            Capability::KeyCertSign => Some(PossibleValue::new("key-cert-sign")),
+            Capability::CrlSign => Some(PossibleValue::new("crl-sign")),
+            Capability::EncipherOnly => Some(PossibleValue::new("encipher-only")),
+            Capability::DecipherOnly => Some(PossibleValue::new("decipher-only")),
+            Capability::Ca => Some(PossibleValue::new("ca")),
+        }
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::DigitalSignature => write!(f, "digital-signature"),
+            Capability::ContentCommitment => write!(f, "content-commitment"),
+            Capability::KeyEncipherment => write!(f, "key-encipherment"),
+            Capability::DataEncipherment => write!(f, "data-encipherment"),
+            Capability::KeyAgreement => write!(f, "key-agreement"),
+            Capability::KeyCertSign => write!(f, "key-cert-sign"),
+            Capability::CrlSign => write!(f, "crl-sign"),
+            Capability::EncipherOnly => write!(f, "encipher-only"),
+            Capability::DecipherOnly => write!(f, "decipher-only"),
+            Capability::Ca => write!(f, "ca"),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+/// The number of characters a challenge string may have
+pub const CHALLENGE_LENGTH: RangeInclusive<usize> = 32..=256;
+
+/// How long a challenge string handed out by stimmgabel can be answered, in seconds
+pub const CHALLENGE_VALIDITY_SECONDS: u64 = 5 * 60;
+
+/// A challenge string handed out by a home server, signed by an actor to prove that they possess
+/// the private key belonging to their ID-Cert
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedChallenge {
+    pub challenge: String,
+    /// The UNIX timestamp after which the challenge string can no longer be answered
+    pub expires: u64,
+    /// The signature over the UTF-8 bytes of the challenge string
+    pub signature: String,
+}
@@ -0,0 +1,252 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use polyproto::der::asn1::{
+    Any, Ia5StringRef, ObjectIdentifier, PrintableStringRef, Utf8StringRef,
+};
+use polyproto::der::{Tag, Tagged};
+use polyproto::RdnSequence;
+
+/// The OID of the UID attribute, which holds the federation ID of an actor
+const UID: ObjectIdentifier = ObjectIdentifier::new_unwrap("0.9.2342.19200300.100.1.1");
+/// The OID of the DC attribute, one of which is given per label of the domain
+const DOMAIN_COMPONENT: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("0.9.2342.19200300.100.1.25");
+
+/// The federation ID of an actor, e.g. `flori@polyphony.chat`. The local name may consist of the
+/// characters `a-z`, `0-9` and `._%+-`, the domain of labels consisting of `a-z`, `0-9` and `-`,
+/// which neither start nor end with a hyphen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FederationId {
+    pub local_name: String,
+    pub domain: String,
+}
+
+/// Why a federation ID is not acceptable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FederationIdError {
+    /// The federation ID itself, or the subject it is read from, is malformed
+    Malformed(String),
+    /// The domain of the federation ID is not the one named by the DC components of the subject, nor
+    /// one of its parent domains
+    DomainMismatch {
+        federation_id: FederationId,
+        domain_components: String,
+    },
+}
+
+impl Display for FederationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.local_name, self.domain)
+    }
+}
+
+impl Display for FederationIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(reason) => write!(f, "{}", reason),
+            Self::DomainMismatch {
+                federation_id,
+                domain_components,
+            } => write!(
+                f,
+                "The UID {} names the domain {}, which is neither {} nor one of its parent domains",
+                federation_id, federation_id.domain, domain_components
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FederationIdError {}
+
+impl FromStr for FederationId {
+    type Err = FederationIdError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let malformed = |reason: String| Err(FederationIdError::Malformed(reason));
+        let Some((local_name, domain)) = value.split_once('@') else {
+            return malformed(format!(
+                "The federation ID {} does not separate the local name from the domain with an @",
+                value
+            ));
+        };
+        if local_name.is_empty() {
+            return malformed(format!("The federation ID {} has no local name", value));
+        }
+        if let Some(character) = local_name
+            .chars()
+            .find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '.' | '_' | '%' | '+' | '-'))
+        {
+            return malformed(format!(
+                "The local name of the federation ID {} contains the illegal character {:?}",
+                value, character
+            ));
+        }
+        for label in domain.split('.') {
+            if label.is_empty() {
+                return malformed(format!(
+                    "The domain of the federation ID {} contains an empty label",
+                    value
+                ));
+            }
+            if let Some(character) = label
+                .chars()
+                .find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '-'))
+            {
+                return malformed(format!(
+                    "The domain of the federation ID {} contains the illegal character {:?}",
+                    value, character
+                ));
+            }
+            if label.starts_with('-') || label.ends_with('-') {
+                return malformed(format!(
+                    "The domain label {} of the federation ID {} starts or ends with a hyphen",
+                    label, value
+                ));
+            }
+        }
+        Ok(Self {
+            local_name: local_name.to_string(),
+            domain: domain.to_string(),
+        })
+    }
+}
+
+impl FederationId {
+    /// Read the federation ID from the UID of `subject` and check that its domain is the one named
+    /// by the DC components of `subject`, or a parent domain of it: `DC=www,DC=polyphony,DC=chat`
+    /// may name the actor `flori@polyphony.chat`, but not `flori@polyphony.social`. Returns `None`
+    /// for subjects without a UID, such as the ones of home servers.
+    pub fn from_subject(subject: &RdnSequence) -> Result<Option<Self>, FederationIdError> {
+        let mut uids = Vec::new();
+        let mut domain_components = Vec::new();
+        // RDN sequences are stored in the reverse order of their string representation
+        for attribute in subject.0.iter().rev().flat_map(|rdn| rdn.0.iter()) {
+            let values = match attribute.oid {
+                UID => &mut uids,
+                DOMAIN_COMPONENT => &mut domain_components,
+                _ => continue,
+            };
+            match attribute_string(&attribute.value) {
+                Some(value) => values.push(value),
+                None => {
+                    return Err(FederationIdError::Malformed(format!(
+                        "The value of the attribute {} is not a string",
+                        attribute.oid
+                    )))
+                }
+            }
+        }
+        let federation_id: Self = match uids.as_slice() {
+            [] => return Ok(None),
+            [uid] => uid.parse()?,
+            _ => {
+                return Err(FederationIdError::Malformed(format!(
+                    "The subject {} names more than one UID",
+                    subject
+                )))
+            }
+        };
+        // The domain of the UID has to match the trailing DC components, label by label
+        let labels: Vec<&str> = federation_id.domain.split('.').collect();
+        let matches = domain_components.len() >= labels.len()
+            && domain_components[domain_components.len() - labels.len()..]
+                .iter()
+                .zip(&labels)
+                .all(|(component, label)| component.eq_ignore_ascii_case(label));
+        let domain = domain_components.join(".");
+        if !matches {
+            return Err(FederationIdError::DomainMismatch {
+                federation_id,
+                domain_components: domain,
+            });
+        }
+        Ok(Some(federation_id))
+    }
+}
+
+/// The value of an attribute of an RDN, if it is one of the string types used in RDN sequences
+pub(crate) fn attribute_string(value: &Any) -> Option<&str> {
+    match value.tag() {
+        Tag::Utf8String => Utf8StringRef::try_from(value).ok().map(|s| s.as_str()),
+        Tag::Ia5String => Ia5StringRef::try_from(value).ok().map(|s| s.as_str()),
+        Tag::PrintableString => PrintableStringRef::try_from(value).ok().map(|s| s.as_str()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use polyproto::RdnSequence;
+
+    use super::{FederationId, FederationIdError};
+
+    #[test]
+    fn federation_id_is_parsed() {
+        let federation_id: FederationId = "flori@polyphony.chat".parse().unwrap();
+        assert_eq!(federation_id.local_name, "flori");
+        assert_eq!(federation_id.domain, "polyphony.chat");
+        assert_eq!(federation_id.to_string(), "flori@polyphony.chat");
+        for malformed in [
+            "flori",
+            "@polyphony.chat",
+            "Flori@polyphony.chat",
+            "flo ri@polyphony.chat",
+            "flori@polyphony..chat",
+            "flori@-polyphony.chat",
+            "flori@polyphony_chat",
+        ] {
+            assert!(
+                matches!(
+                    malformed.parse::<FederationId>(),
+                    Err(FederationIdError::Malformed(_))
+                ),
+                "{} was accepted",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn uid_has_to_match_trailing_domain_components() {
+        let subject = |subject: &str| RdnSequence::from_str(subject).unwrap();
+        let federation_id = FederationId::from_subject(&subject(
+            "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+        ))
+        .unwrap();
+        assert_eq!(
+            federation_id.map(|id| id.to_string()).as_deref(),
+            Some("flori@polyphony.chat")
+        );
+        assert_eq!(
+            FederationId::from_subject(&subject("DC=polyphony,DC=chat")),
+            Ok(None)
+        );
+        // The DC components may name a subdomain of the domain of the UID
+        assert!(FederationId::from_subject(&subject(
+            "CN=flori,DC=www,DC=polyphony,DC=chat,UID=flori@polyphony.chat",
+        ))
+        .is_ok());
+        for mismatch in [
+            "CN=flori,DC=polyphony,DC=social,UID=flori@polyphony.chat",
+            "CN=flori,DC=chat,UID=flori@polyphony.chat",
+            "CN=flori,DC=notpolyphony,DC=chat,UID=flori@polyphony.chat",
+            "CN=flori,DC=polyphony,DC=chat,UID=flori@www.polyphony.chat",
+        ] {
+            assert!(
+                matches!(
+                    FederationId::from_subject(&subject(mismatch)),
+                    Err(FederationIdError::DomainMismatch { .. })
+                ),
+                "{} was accepted",
+                mismatch
+            );
+        }
+    }
+}
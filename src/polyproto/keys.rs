@@ -2,15 +2,22 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use ed25519_dalek::ed25519::signature::SignerMut;
+use std::str::FromStr;
+
+use ed25519_dalek::ed25519::signature::{SignerMut, Verifier};
 use ed25519_dalek::{SigningKey, VerifyingKey};
+use fips204::traits::{KeyGen, SerDes};
 use polyproto::certs::PublicKeyInfo;
-use polyproto::der::asn1::BitString;
+use polyproto::der::asn1::{Any, BitString, ObjectIdentifier};
 use polyproto::signature::Signature;
+use polyproto::spki::AlgorithmIdentifierOwned;
 #[cfg(test)]
 use rand::rngs::OsRng;
 
-use super::signature::SignatureEd25519;
+use super::signature::{
+    SignatureEcdsaP256, SignatureEcdsaP384, SignatureEd25519, SignatureEd448, SignatureMlDsa44,
+    SignatureMlDsa65,
+};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct PrivateKeyEd25519 {
@@ -46,20 +53,20 @@ impl polyproto::key::PublicKey<SignatureEd25519> for PublicKeyEd25519 {
     fn try_from_public_key_info(
         public_key_info: polyproto::certs::PublicKeyInfo,
     ) -> Result<Self, polyproto::errors::composite::ConversionError> {
-        let mut key_vec = public_key_info.public_key_bitstring.raw_bytes().to_vec();
-        key_vec.resize(32, 0);
-        let signature_array: [u8; 32] = {
-            let mut array = [0; 32];
-            array.copy_from_slice(&key_vec[..]);
-            array
-        };
-        match VerifyingKey::from_bytes(&signature_array) {
-            Ok(key) => Ok(PublicKeyEd25519 { key }),
-            Err(e) => Err(polyproto::errors::composite::ConversionError::InvalidInput(
+        let key_bytes = public_key_info.public_key_bitstring.raw_bytes();
+        let signature_array: [u8; 32] = key_bytes.try_into().map_err(|_| {
+            polyproto::errors::composite::ConversionError::InvalidInput(
                 polyproto::errors::base::InvalidInput::Malformed(format!(
-                    "Could not convert public key: {}",
-                    e
+                    "Expected an Ed25519 public key of 32 bytes, got {} bytes",
+                    key_bytes.len()
                 )),
+            )
+        })?;
+        match VerifyingKey::from_bytes(&signature_array) {
+            Ok(key) => Ok(PublicKeyEd25519 { key }),
+            // The key has the right length, but is not a valid point on the curve
+            Err(_) => Err(polyproto::errors::composite::ConversionError::IdCertError(
+                polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo,
             )),
         }
     }
@@ -80,6 +87,499 @@ impl polyproto::key::PrivateKey<crate::polyproto::signature::SignatureEd25519>
     }
 }
 
+impl From<SigningKey> for PrivateKeyEd25519 {
+    fn from(key: SigningKey) -> Self {
+        let public_key = PublicKeyEd25519 {
+            key: key.verifying_key(),
+        };
+        Self { public_key, key }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrivateKeyEd448 {
+    pub public_key: PublicKeyEd448,
+    pub(crate) key: ed448_goldilocks_plus::SigningKey,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKeyEd448 {
+    pub key: ed448_goldilocks_plus::VerifyingKey,
+}
+
+impl polyproto::key::PublicKey<SignatureEd448> for PublicKeyEd448 {
+    fn verify_signature(
+        &self,
+        signature: &SignatureEd448,
+        data: &[u8],
+    ) -> Result<(), polyproto::errors::composite::PublicKeyError> {
+        match self.key.verify(data, signature.as_signature()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(polyproto::errors::composite::PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> polyproto::certs::PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: SignatureEd448::algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(&self.key.to_bytes()).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(
+        public_key_info: polyproto::certs::PublicKeyInfo,
+    ) -> Result<Self, polyproto::errors::composite::ConversionError> {
+        let key_bytes = public_key_info.public_key_bitstring.raw_bytes();
+        let key_array: [u8; 57] = key_bytes.try_into().map_err(|_| {
+            polyproto::errors::composite::ConversionError::InvalidInput(
+                polyproto::errors::base::InvalidInput::Malformed(format!(
+                    "Expected an Ed448 public key of 57 bytes, got {} bytes",
+                    key_bytes.len()
+                )),
+            )
+        })?;
+        match ed448_goldilocks_plus::VerifyingKey::from_bytes(&key_array) {
+            Ok(key) => Ok(PublicKeyEd448 { key }),
+            // The key has the right length, but is not a valid point on the curve
+            Err(_) => Err(polyproto::errors::composite::ConversionError::IdCertError(
+                polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo,
+            )),
+        }
+    }
+}
+
+impl polyproto::key::PrivateKey<SignatureEd448> for PrivateKeyEd448 {
+    type PublicKey = PublicKeyEd448;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> SignatureEd448 {
+        // Signer is called explicitly, as SignerMut::sign would require a mutable key
+        SignatureEd448 {
+            signature: ed25519_dalek::ed25519::signature::Signer::sign(&self.key, data),
+        }
+    }
+}
+
+impl From<ed448_goldilocks_plus::SigningKey> for PrivateKeyEd448 {
+    fn from(key: ed448_goldilocks_plus::SigningKey) -> Self {
+        let public_key = PublicKeyEd448 {
+            key: key.verifying_key(),
+        };
+        Self { public_key, key }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrivateKeyEcdsaP256 {
+    pub public_key: PublicKeyEcdsaP256,
+    pub(crate) key: p256::ecdsa::SigningKey,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKeyEcdsaP256 {
+    pub key: p256::ecdsa::VerifyingKey,
+}
+
+impl PublicKeyEcdsaP256 {
+    /// The OID of the P-256 curve, which is the parameter of the public key's algorithm
+    const CURVE: &'static str = "1.2.840.10045.3.1.7";
+
+    /// The algorithm identifier of the public key info of P-256 keys
+    pub fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        ec_public_key_algorithm_identifier(Self::CURVE)
+    }
+}
+
+impl polyproto::key::PublicKey<SignatureEcdsaP256> for PublicKeyEcdsaP256 {
+    fn verify_signature(
+        &self,
+        signature: &SignatureEcdsaP256,
+        data: &[u8],
+    ) -> Result<(), polyproto::errors::composite::PublicKeyError> {
+        // A signature which is not valid DER cannot be valid for any data
+        let signature = p256::ecdsa::Signature::from_der(signature.as_signature())
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadSignature)?;
+        match self.key.verify(data, &signature) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(polyproto::errors::composite::PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> polyproto::certs::PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: Self::algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(
+                self.key.to_encoded_point(false).as_bytes(),
+            )
+            .unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(
+        public_key_info: polyproto::certs::PublicKeyInfo,
+    ) -> Result<Self, polyproto::errors::composite::ConversionError> {
+        check_ec_public_key_algorithm(&public_key_info.algorithm, Self::CURVE, "P-256")?;
+        match p256::ecdsa::VerifyingKey::from_sec1_bytes(
+            public_key_info.public_key_bitstring.raw_bytes(),
+        ) {
+            Ok(key) => Ok(PublicKeyEcdsaP256 { key }),
+            // The bytes do not encode a point on the curve
+            Err(_) => Err(polyproto::errors::composite::ConversionError::IdCertError(
+                polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo,
+            )),
+        }
+    }
+}
+
+impl polyproto::key::PrivateKey<SignatureEcdsaP256> for PrivateKeyEcdsaP256 {
+    type PublicKey = PublicKeyEcdsaP256;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> SignatureEcdsaP256 {
+        // The message is hashed with SHA-256 before signing
+        let signature: p256::ecdsa::Signature =
+            ed25519_dalek::ed25519::signature::Signer::sign(&self.key, data);
+        SignatureEcdsaP256 {
+            signature: signature.to_der().as_bytes().to_vec(),
+        }
+    }
+}
+
+impl From<p256::ecdsa::SigningKey> for PrivateKeyEcdsaP256 {
+    fn from(key: p256::ecdsa::SigningKey) -> Self {
+        let public_key = PublicKeyEcdsaP256 {
+            key: key.verifying_key().clone(),
+        };
+        Self { public_key, key }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrivateKeyEcdsaP384 {
+    pub public_key: PublicKeyEcdsaP384,
+    pub(crate) key: p384::ecdsa::SigningKey,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKeyEcdsaP384 {
+    pub key: p384::ecdsa::VerifyingKey,
+}
+
+impl PublicKeyEcdsaP384 {
+    /// The OID of the P-384 curve, which is the parameter of the public key's algorithm
+    const CURVE: &'static str = "1.3.132.0.34";
+
+    /// The algorithm identifier of the public key info of P-384 keys
+    pub fn algorithm_identifier() -> AlgorithmIdentifierOwned {
+        ec_public_key_algorithm_identifier(Self::CURVE)
+    }
+}
+
+impl polyproto::key::PublicKey<SignatureEcdsaP384> for PublicKeyEcdsaP384 {
+    fn verify_signature(
+        &self,
+        signature: &SignatureEcdsaP384,
+        data: &[u8],
+    ) -> Result<(), polyproto::errors::composite::PublicKeyError> {
+        // A signature which is not valid DER cannot be valid for any data
+        let signature = p384::ecdsa::Signature::from_der(signature.as_signature())
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadSignature)?;
+        match self.key.verify(data, &signature) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(polyproto::errors::composite::PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> polyproto::certs::PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: Self::algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(
+                self.key.to_encoded_point(false).as_bytes(),
+            )
+            .unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(
+        public_key_info: polyproto::certs::PublicKeyInfo,
+    ) -> Result<Self, polyproto::errors::composite::ConversionError> {
+        check_ec_public_key_algorithm(&public_key_info.algorithm, Self::CURVE, "P-384")?;
+        match p384::ecdsa::VerifyingKey::from_sec1_bytes(
+            public_key_info.public_key_bitstring.raw_bytes(),
+        ) {
+            Ok(key) => Ok(PublicKeyEcdsaP384 { key }),
+            // The bytes do not encode a point on the curve
+            Err(_) => Err(polyproto::errors::composite::ConversionError::IdCertError(
+                polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo,
+            )),
+        }
+    }
+}
+
+impl polyproto::key::PrivateKey<SignatureEcdsaP384> for PrivateKeyEcdsaP384 {
+    type PublicKey = PublicKeyEcdsaP384;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> SignatureEcdsaP384 {
+        // The message is hashed with SHA-384 before signing
+        let signature: p384::ecdsa::Signature =
+            ed25519_dalek::ed25519::signature::Signer::sign(&self.key, data);
+        SignatureEcdsaP384 {
+            signature: signature.to_der().as_bytes().to_vec(),
+        }
+    }
+}
+
+impl From<p384::ecdsa::SigningKey> for PrivateKeyEcdsaP384 {
+    fn from(key: p384::ecdsa::SigningKey) -> Self {
+        let public_key = PublicKeyEcdsaP384 {
+            key: key.verifying_key().clone(),
+        };
+        Self { public_key, key }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrivateKeyMlDsa44 {
+    pub public_key: PublicKeyMlDsa44,
+    /// The 32 byte seed the key pair is derived from
+    pub(crate) seed: [u8; 32],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKeyMlDsa44 {
+    /// The encoded public key, which is 1312 bytes long
+    pub key: Vec<u8>,
+}
+
+impl PrivateKeyMlDsa44 {
+    /// Derive the key pair from the 32 byte seed `ξ`, as described in FIPS 204
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let (public_key, _) = fips204::ml_dsa_44::KG::keygen_from_seed(&seed);
+        Self {
+            public_key: PublicKeyMlDsa44 {
+                key: public_key.into_bytes().to_vec(),
+            },
+            seed,
+        }
+    }
+}
+
+impl polyproto::key::PublicKey<SignatureMlDsa44> for PublicKeyMlDsa44 {
+    fn verify_signature(
+        &self,
+        signature: &SignatureMlDsa44,
+        data: &[u8],
+    ) -> Result<(), polyproto::errors::composite::PublicKeyError> {
+        let key_bytes: [u8; fips204::ml_dsa_44::PK_LEN] = self
+            .key
+            .as_slice()
+            .try_into()
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo)?;
+        let key = fips204::ml_dsa_44::PublicKey::try_from_bytes(key_bytes)
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo)?;
+        // A signature of the wrong length cannot be valid for any data
+        let signature: [u8; fips204::ml_dsa_44::SIG_LEN] = signature
+            .as_signature()
+            .as_slice()
+            .try_into()
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadSignature)?;
+        // polyproto uses pure ML-DSA with an empty context string
+        match fips204::traits::Verifier::verify(&key, data, &signature, &[]) {
+            true => Ok(()),
+            false => Err(polyproto::errors::composite::PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> polyproto::certs::PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: SignatureMlDsa44::algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(&self.key).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(
+        public_key_info: polyproto::certs::PublicKeyInfo,
+    ) -> Result<Self, polyproto::errors::composite::ConversionError> {
+        let key_bytes = public_key_info.public_key_bitstring.raw_bytes();
+        let key_array: [u8; fips204::ml_dsa_44::PK_LEN] = key_bytes.try_into().map_err(|_| {
+            polyproto::errors::composite::ConversionError::InvalidInput(
+                polyproto::errors::base::InvalidInput::Malformed(format!(
+                    "Expected an ML-DSA-44 public key of {} bytes, got {} bytes",
+                    fips204::ml_dsa_44::PK_LEN,
+                    key_bytes.len()
+                )),
+            )
+        })?;
+        match fips204::ml_dsa_44::PublicKey::try_from_bytes(key_array) {
+            Ok(_) => Ok(PublicKeyMlDsa44 {
+                key: key_bytes.to_vec(),
+            }),
+            Err(_) => Err(polyproto::errors::composite::ConversionError::IdCertError(
+                polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo,
+            )),
+        }
+    }
+}
+
+impl polyproto::key::PrivateKey<SignatureMlDsa44> for PrivateKeyMlDsa44 {
+    type PublicKey = PublicKeyMlDsa44;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> SignatureMlDsa44 {
+        let (_, private_key) = fips204::ml_dsa_44::KG::keygen_from_seed(&self.seed);
+        // Signing only fails if the random number generator fails, which the PrivateKey trait
+        // gives us no way to report
+        let signature = fips204::traits::Signer::try_sign(&private_key, data, &[])
+            .expect("Could not sign with ML-DSA-44");
+        SignatureMlDsa44 {
+            signature: signature.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrivateKeyMlDsa65 {
+    pub public_key: PublicKeyMlDsa65,
+    /// The 32 byte seed the key pair is derived from
+    pub(crate) seed: [u8; 32],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PublicKeyMlDsa65 {
+    /// The encoded public key, which is 1952 bytes long
+    pub key: Vec<u8>,
+}
+
+impl PrivateKeyMlDsa65 {
+    /// Derive the key pair from the 32 byte seed `ξ`, as described in FIPS 204
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let (public_key, _) = fips204::ml_dsa_65::KG::keygen_from_seed(&seed);
+        Self {
+            public_key: PublicKeyMlDsa65 {
+                key: public_key.into_bytes().to_vec(),
+            },
+            seed,
+        }
+    }
+}
+
+impl polyproto::key::PublicKey<SignatureMlDsa65> for PublicKeyMlDsa65 {
+    fn verify_signature(
+        &self,
+        signature: &SignatureMlDsa65,
+        data: &[u8],
+    ) -> Result<(), polyproto::errors::composite::PublicKeyError> {
+        let key_bytes: [u8; fips204::ml_dsa_65::PK_LEN] = self
+            .key
+            .as_slice()
+            .try_into()
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo)?;
+        let key = fips204::ml_dsa_65::PublicKey::try_from_bytes(key_bytes)
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo)?;
+        // A signature of the wrong length cannot be valid for any data
+        let signature: [u8; fips204::ml_dsa_65::SIG_LEN] = signature
+            .as_signature()
+            .as_slice()
+            .try_into()
+            .map_err(|_| polyproto::errors::composite::PublicKeyError::BadSignature)?;
+        // polyproto uses pure ML-DSA with an empty context string
+        match fips204::traits::Verifier::verify(&key, data, &signature, &[]) {
+            true => Ok(()),
+            false => Err(polyproto::errors::composite::PublicKeyError::BadSignature),
+        }
+    }
+
+    fn public_key_info(&self) -> polyproto::certs::PublicKeyInfo {
+        PublicKeyInfo {
+            algorithm: SignatureMlDsa65::algorithm_identifier(),
+            public_key_bitstring: BitString::from_bytes(&self.key).unwrap(),
+        }
+    }
+
+    fn try_from_public_key_info(
+        public_key_info: polyproto::certs::PublicKeyInfo,
+    ) -> Result<Self, polyproto::errors::composite::ConversionError> {
+        let key_bytes = public_key_info.public_key_bitstring.raw_bytes();
+        let key_array: [u8; fips204::ml_dsa_65::PK_LEN] = key_bytes.try_into().map_err(|_| {
+            polyproto::errors::composite::ConversionError::InvalidInput(
+                polyproto::errors::base::InvalidInput::Malformed(format!(
+                    "Expected an ML-DSA-65 public key of {} bytes, got {} bytes",
+                    fips204::ml_dsa_65::PK_LEN,
+                    key_bytes.len()
+                )),
+            )
+        })?;
+        match fips204::ml_dsa_65::PublicKey::try_from_bytes(key_array) {
+            Ok(_) => Ok(PublicKeyMlDsa65 {
+                key: key_bytes.to_vec(),
+            }),
+            Err(_) => Err(polyproto::errors::composite::ConversionError::IdCertError(
+                polyproto::errors::composite::PublicKeyError::BadPublicKeyInfo,
+            )),
+        }
+    }
+}
+
+impl polyproto::key::PrivateKey<SignatureMlDsa65> for PrivateKeyMlDsa65 {
+    type PublicKey = PublicKeyMlDsa65;
+
+    fn pubkey(&self) -> &Self::PublicKey {
+        &self.public_key
+    }
+
+    fn sign(&self, data: &[u8]) -> SignatureMlDsa65 {
+        let (_, private_key) = fips204::ml_dsa_65::KG::keygen_from_seed(&self.seed);
+        // Signing only fails if the random number generator fails, which the PrivateKey trait
+        // gives us no way to report
+        let signature = fips204::traits::Signer::try_sign(&private_key, data, &[])
+            .expect("Could not sign with ML-DSA-65");
+        SignatureMlDsa65 {
+            signature: signature.to_vec(),
+        }
+    }
+}
+
+/// The algorithm identifier of elliptic curve public keys, `id-ecPublicKey`, with the OID of the named
+/// curve as its parameter
+fn ec_public_key_algorithm_identifier(curve: &str) -> AlgorithmIdentifierOwned {
+    // Unwraps are ok because the OIDs are hard-coded and cannot be invalid
+    let curve = ObjectIdentifier::from_str(curve).unwrap();
+    AlgorithmIdentifierOwned {
+        oid: ObjectIdentifier::from_str("1.2.840.10045.2.1").unwrap(),
+        parameters: Some(Any::encode_from(&curve).unwrap()),
+    }
+}
+
+/// Check that `algorithm` identifies an elliptic curve public key on the given named curve
+fn check_ec_public_key_algorithm(
+    algorithm: &AlgorithmIdentifierOwned,
+    curve: &str,
+    curve_name: &str,
+) -> Result<(), polyproto::errors::composite::ConversionError> {
+    if *algorithm != ec_public_key_algorithm_identifier(curve) {
+        return Err(polyproto::errors::composite::ConversionError::InvalidInput(
+            polyproto::errors::base::InvalidInput::Malformed(format!(
+                "Expected a public key on the {} curve, got algorithm {}",
+                curve_name, algorithm.oid
+            )),
+        ));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 impl PrivateKeyEd25519 {
     pub fn gen_keypair(csprng: &mut OsRng) -> Self {
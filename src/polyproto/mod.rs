@@ -2,6 +2,9 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod challenge;
+pub mod federation_id;
 pub mod keys;
 pub mod message;
+pub mod session_id;
 pub mod signature;
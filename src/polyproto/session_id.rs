@@ -0,0 +1,176 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+
+use polyproto::der::asn1::ObjectIdentifier;
+use polyproto::RdnSequence;
+
+use crate::options::Target;
+use crate::polyproto::federation_id::attribute_string;
+
+/// The OID of the uniqueIdentifier attribute, which holds the session ID of an actor
+const UNIQUE_IDENTIFIER: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("0.9.2342.19200300.100.1.44");
+
+/// The number of characters a session ID may have
+pub const SESSION_ID_LENGTH: RangeInclusive<usize> = 1..=32;
+
+/// A rule for session IDs, which the subject of an ID-Cert or ID-CSR breaks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionIdError {
+    /// Actors have to name exactly one session ID, home servers none
+    Presence(String),
+    /// The session ID is not [SESSION_ID_LENGTH] characters long
+    Length(String),
+    /// The session ID contains characters other than `A-Z`, `a-z`, `0-9`, `-` and `_`
+    Characters(String),
+}
+
+impl SessionIdError {
+    /// The name of the broken rule, which the failed check is named after
+    pub fn rule(&self) -> &'static str {
+        match self {
+            Self::Presence(_) => "presence",
+            Self::Length(_) => "length",
+            Self::Characters(_) => "characters",
+        }
+    }
+}
+
+impl Display for SessionIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Presence(reason) | Self::Length(reason) | Self::Characters(reason) => {
+                write!(f, "{}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SessionIdError {}
+
+/// Read the session ID from the uniqueIdentifier of `subject` and check it against the rules for
+/// `target`: Actors have to name exactly one session ID of [SESSION_ID_LENGTH] characters, which
+/// may be `A-Z`, `a-z`, `0-9`, `-` and `_`. Home servers have no sessions and must not name one.
+/// Returns the session ID, if there is one.
+pub fn session_id_of(
+    subject: &RdnSequence,
+    target: Target,
+) -> Result<Option<&str>, SessionIdError> {
+    let mut session_ids = Vec::new();
+    for attribute in subject.0.iter().flat_map(|rdn| rdn.0.iter()) {
+        if attribute.oid != UNIQUE_IDENTIFIER {
+            continue;
+        }
+        match attribute_string(&attribute.value) {
+            Some(session_id) => session_ids.push(session_id),
+            None => {
+                return Err(SessionIdError::Characters(
+                    "The session ID is not a string".to_string(),
+                ))
+            }
+        }
+    }
+    let session_id = match (target, session_ids.as_slice()) {
+        (Target::Homeserver, []) => return Ok(None),
+        (Target::Homeserver, [session_id, ..]) => {
+            return Err(SessionIdError::Presence(format!(
+                "Home servers have no sessions, but the subject names the session ID {}",
+                session_id
+            )))
+        }
+        (Target::Actor, [session_id]) => *session_id,
+        (Target::Actor, []) => {
+            return Err(SessionIdError::Presence(
+                "The subject of an actor has to name a session ID as its uniqueIdentifier"
+                    .to_string(),
+            ))
+        }
+        (Target::Actor, _) => {
+            return Err(SessionIdError::Presence(format!(
+                "The subject of an actor has to name exactly one session ID, got {}",
+                session_ids.len()
+            )))
+        }
+    };
+    let length = session_id.chars().count();
+    if !SESSION_ID_LENGTH.contains(&length) {
+        return Err(SessionIdError::Length(format!(
+            "Session IDs have to be {} to {} characters long, got {}",
+            SESSION_ID_LENGTH.start(),
+            SESSION_ID_LENGTH.end(),
+            length
+        )));
+    }
+    if let Some(character) = session_id
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_')))
+    {
+        return Err(SessionIdError::Characters(format!(
+            "The session ID {} contains the illegal character {:?}",
+            session_id, character
+        )));
+    }
+    Ok(Some(session_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use polyproto::RdnSequence;
+
+    use crate::options::Target;
+
+    use super::session_id_of;
+
+    #[test]
+    fn session_ids_follow_the_rules_of_the_target() {
+        let rule = |subject: &str, target: Target| {
+            session_id_of(&RdnSequence::from_str(subject).unwrap(), target)
+                .map(|session_id| session_id.map(str::to_string))
+                .map_err(|error| error.rule())
+        };
+        let actor = "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat";
+        assert_eq!(
+            rule(
+                &format!("{},uniqueIdentifier=client1", actor),
+                Target::Actor
+            ),
+            Ok(Some("client1".to_string()))
+        );
+        assert_eq!(rule(actor, Target::Actor), Err("presence"));
+        assert_eq!(
+            rule(
+                &format!("{},uniqueIdentifier=a,uniqueIdentifier=b", actor),
+                Target::Actor
+            ),
+            Err("presence")
+        );
+        assert_eq!(
+            rule(
+                &format!("{},uniqueIdentifier={}", actor, "a".repeat(33)),
+                Target::Actor
+            ),
+            Err("length")
+        );
+        assert_eq!(
+            rule(
+                &format!("{},uniqueIdentifier=client/1", actor),
+                Target::Actor
+            ),
+            Err("characters")
+        );
+        assert_eq!(rule("DC=polyphony,DC=chat", Target::Homeserver), Ok(None));
+        assert_eq!(
+            rule(
+                "DC=polyphony,DC=chat,uniqueIdentifier=client1",
+                Target::Homeserver
+            ),
+            Err("presence")
+        );
+    }
+}
@@ -33,6 +33,12 @@ impl Signature for SignatureEd25519 {
         }
     }
 
+    /// Create a signature from its raw bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `signature` is not exactly 64 bytes long. Callers handling untrusted input must
+    /// check the length beforehand.
     fn from_bytes(signature: &[u8]) -> Self {
         let mut sig = [0u8; 64];
         sig.copy_from_slice(signature);
@@ -47,3 +53,222 @@ impl polyproto::spki::SignatureBitStringEncoding for SignatureEd25519 {
         polyproto::der::asn1::BitString::from_bytes(self.signature.to_bytes().as_slice())
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureEd448 {
+    pub(crate) signature: ed448_goldilocks_plus::Signature,
+}
+
+impl std::fmt::Display for SignatureEd448 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.signature)
+    }
+}
+
+impl Signature for SignatureEd448 {
+    type Signature = ed448_goldilocks_plus::Signature;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    fn algorithm_identifier() -> polyproto::spki::AlgorithmIdentifierOwned {
+        polyproto::spki::AlgorithmIdentifierOwned {
+            // Unwrap is ok because the OID is hard-coded and cannot be invalid
+            oid: polyproto::der::asn1::ObjectIdentifier::from_str("1.3.101.113").unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Create a signature from its raw bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `signature` is not a 114 byte long, valid Ed448 signature. Callers handling
+    /// untrusted input must check the signature beforehand.
+    fn from_bytes(signature: &[u8]) -> Self {
+        SignatureEd448 {
+            signature: ed448_goldilocks_plus::Signature::try_from(signature).unwrap(),
+        }
+    }
+}
+
+impl polyproto::spki::SignatureBitStringEncoding for SignatureEd448 {
+    fn to_bitstring(&self) -> polyproto::der::Result<polyproto::der::asn1::BitString> {
+        polyproto::der::asn1::BitString::from_bytes(self.signature.to_bytes().as_slice())
+    }
+}
+
+/// An ECDSA signature over the NIST P-256 curve, using SHA-256 as the hash function. The signature
+/// is kept in its DER encoded form, as found in certificates, and only parsed when it is verified.
+/// This way, malformed signatures are reported as bad signatures instead of failing to parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureEcdsaP256 {
+    pub(crate) signature: Vec<u8>,
+}
+
+impl std::fmt::Display for SignatureEcdsaP256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.signature))
+    }
+}
+
+impl Signature for SignatureEcdsaP256 {
+    type Signature = Vec<u8>;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    fn algorithm_identifier() -> polyproto::spki::AlgorithmIdentifierOwned {
+        polyproto::spki::AlgorithmIdentifierOwned {
+            // ecdsa-with-SHA256. Unwrap is ok because the OID is hard-coded and cannot be invalid
+            oid: polyproto::der::asn1::ObjectIdentifier::from_str("1.2.840.10045.4.3.2").unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Create a signature from its DER encoded bytes. The bytes are not checked until the signature
+    /// is verified.
+    fn from_bytes(signature: &[u8]) -> Self {
+        SignatureEcdsaP256 {
+            signature: signature.to_vec(),
+        }
+    }
+}
+
+impl polyproto::spki::SignatureBitStringEncoding for SignatureEcdsaP256 {
+    fn to_bitstring(&self) -> polyproto::der::Result<polyproto::der::asn1::BitString> {
+        polyproto::der::asn1::BitString::from_bytes(&self.signature)
+    }
+}
+
+/// An ECDSA signature over the NIST P-384 curve, using SHA-384 as the hash function. Like
+/// [SignatureEcdsaP256], the signature is kept in its DER encoded form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureEcdsaP384 {
+    pub(crate) signature: Vec<u8>,
+}
+
+impl std::fmt::Display for SignatureEcdsaP384 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.signature))
+    }
+}
+
+impl Signature for SignatureEcdsaP384 {
+    type Signature = Vec<u8>;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    fn algorithm_identifier() -> polyproto::spki::AlgorithmIdentifierOwned {
+        polyproto::spki::AlgorithmIdentifierOwned {
+            // ecdsa-with-SHA384. Unwrap is ok because the OID is hard-coded and cannot be invalid
+            oid: polyproto::der::asn1::ObjectIdentifier::from_str("1.2.840.10045.4.3.3").unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Create a signature from its DER encoded bytes. The bytes are not checked until the signature
+    /// is verified.
+    fn from_bytes(signature: &[u8]) -> Self {
+        SignatureEcdsaP384 {
+            signature: signature.to_vec(),
+        }
+    }
+}
+
+impl polyproto::spki::SignatureBitStringEncoding for SignatureEcdsaP384 {
+    fn to_bitstring(&self) -> polyproto::der::Result<polyproto::der::asn1::BitString> {
+        polyproto::der::asn1::BitString::from_bytes(&self.signature)
+    }
+}
+
+/// An ML-DSA-44 signature, as specified in FIPS 204. ML-DSA-44 signatures are 2420 bytes long.
+/// Like ECDSA signatures, the bytes are only checked when the signature is verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureMlDsa44 {
+    pub(crate) signature: Vec<u8>,
+}
+
+impl std::fmt::Display for SignatureMlDsa44 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.signature))
+    }
+}
+
+impl Signature for SignatureMlDsa44 {
+    type Signature = Vec<u8>;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    fn algorithm_identifier() -> polyproto::spki::AlgorithmIdentifierOwned {
+        polyproto::spki::AlgorithmIdentifierOwned {
+            // id-ml-dsa-44. Unwrap is ok because the OID is hard-coded and cannot be invalid
+            oid: polyproto::der::asn1::ObjectIdentifier::from_str("2.16.840.1.101.3.4.3.17")
+                .unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Create a signature from its raw bytes. The bytes are not checked until the signature is
+    /// verified.
+    fn from_bytes(signature: &[u8]) -> Self {
+        SignatureMlDsa44 {
+            signature: signature.to_vec(),
+        }
+    }
+}
+
+impl polyproto::spki::SignatureBitStringEncoding for SignatureMlDsa44 {
+    fn to_bitstring(&self) -> polyproto::der::Result<polyproto::der::asn1::BitString> {
+        polyproto::der::asn1::BitString::from_bytes(&self.signature)
+    }
+}
+
+/// An ML-DSA-65 signature, as specified in FIPS 204. ML-DSA-65 signatures are 3309 bytes long.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureMlDsa65 {
+    pub(crate) signature: Vec<u8>,
+}
+
+impl std::fmt::Display for SignatureMlDsa65 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.signature))
+    }
+}
+
+impl Signature for SignatureMlDsa65 {
+    type Signature = Vec<u8>;
+
+    fn as_signature(&self) -> &Self::Signature {
+        &self.signature
+    }
+
+    fn algorithm_identifier() -> polyproto::spki::AlgorithmIdentifierOwned {
+        polyproto::spki::AlgorithmIdentifierOwned {
+            // id-ml-dsa-65. Unwrap is ok because the OID is hard-coded and cannot be invalid
+            oid: polyproto::der::asn1::ObjectIdentifier::from_str("2.16.840.1.101.3.4.3.18")
+                .unwrap(),
+            parameters: None,
+        }
+    }
+
+    /// Create a signature from its raw bytes. The bytes are not checked until the signature is
+    /// verified.
+    fn from_bytes(signature: &[u8]) -> Self {
+        SignatureMlDsa65 {
+            signature: signature.to_vec(),
+        }
+    }
+}
+
+impl polyproto::spki::SignatureBitStringEncoding for SignatureMlDsa65 {
+    fn to_bitstring(&self) -> polyproto::der::Result<polyproto::der::asn1::BitString> {
+        polyproto::der::asn1::BitString::from_bytes(&self.signature)
+    }
+}
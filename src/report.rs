@@ -0,0 +1,96 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+
+use log::*;
+use serde::Serialize;
+
+/// A machine-readable account of a verification run: What was verified, which checks were
+/// performed and why the verification failed, if it did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// The verification mode, e.g. `id-cert`
+    pub mode: String,
+    /// The signature algorithm the value was verified with, if it could be determined
+    pub algorithm: Option<String>,
+    /// The subject of the verified value, if it could be parsed and has one
+    pub subject: Option<String>,
+    /// The issuer of the verified value, if it could be parsed and has one
+    pub issuer: Option<String>,
+    /// The capabilities granted by the verified ID-Cert, if they were inspected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<GrantedCapabilities>,
+    /// The checks performed, in the order they were performed in. Verification stops at the first
+    /// failed check
+    pub checks: Vec<Check>,
+    /// The exit code stimmgabel exits with. See [crate::errors::ExitCode]
+    pub exit_code: i32,
+}
+
+/// The capabilities an ID-Cert grants, as decoded from its key usage and basic constraints
+/// extensions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GrantedCapabilities {
+    /// The granted key usages, e.g. `digital-signature`
+    pub key_usage: Vec<String>,
+    /// Whether the ID-Cert may act as a CA
+    pub ca: bool,
+    /// How many CAs may follow the ID-Cert in a chain, if it is limited
+    pub path_length: Option<u8>,
+}
+
+/// A single check performed during verification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    /// The reason the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Report {
+    /// Create a new, empty report for the given verification mode
+    pub fn new(mode: &str) -> Self {
+        Self {
+            mode: mode.to_string(),
+            algorithm: None,
+            subject: None,
+            issuer: None,
+            capabilities: None,
+            checks: Vec::new(),
+            exit_code: 0,
+        }
+    }
+
+    /// Record the signature algorithm the value was verified with
+    pub fn with_algorithm(mut self, algorithm: impl Display) -> Self {
+        self.algorithm = Some(algorithm.to_string());
+        self
+    }
+
+    /// Record that the check `name` passed
+    pub fn pass(&mut self, name: &str) {
+        debug!("Check {} passed", name);
+        self.checks.push(Check {
+            name: name.to_string(),
+            passed: true,
+            error: None,
+        });
+    }
+
+    /// Record that the check `name` failed with the given exit code and reason. Returns the report,
+    /// since no further checks are performed after a failed one.
+    pub fn fail(mut self, name: &str, exit_code: i32, error: impl Display) -> Self {
+        error!("Check {} failed: {}", name, error);
+        self.checks.push(Check {
+            name: name.to_string(),
+            passed: false,
+            error: Some(error.to_string()),
+        });
+        self.exit_code = exit_code;
+        self
+    }
+}
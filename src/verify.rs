@@ -0,0 +1,1516 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Verification of polyproto values. The functions in this module perform the same checks as the
+//! `verify` command and return a [Report] of them, which can be used to run stimmgabel in-process,
+//! e.g. from the test suite of another polyproto implementation.
+
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use polyproto::certs::capabilities::Capabilities;
+use polyproto::certs::idcert::IdCert;
+use polyproto::certs::idcsr::IdCsr;
+use polyproto::der::oid::AssociatedOid;
+use polyproto::der::{Decode, DecodeOwned};
+use polyproto::errors::composite::ConversionError;
+use polyproto::key::PublicKey;
+use polyproto::RdnSequence;
+use x509_cert::ext::pkix::{BasicConstraints, KeyUsage, KeyUsages};
+use x509_cert::ext::Extension;
+
+use crate::algorithm::{
+    check_signature, detect_algorithm, with_algorithm, Algorithm, DetectedAlgorithm,
+};
+use crate::encoding::{decode_base64, decode_der};
+use crate::errors::{
+    conversion_error_to_exit_code, federation_id_error_to_exit_code, public_key_error_to_exit_code,
+    ExitCode,
+};
+use crate::keyring::{KeyFile, KeyRing};
+use crate::options::{Capability, Format, SignatureAlgorithm, Target};
+use crate::polyproto::challenge::{SignedChallenge, CHALLENGE_LENGTH};
+use crate::polyproto::federation_id::FederationId;
+use crate::polyproto::message::Message;
+use crate::polyproto::session_id::session_id_of;
+use crate::report::{Check, GrantedCapabilities, Report};
+
+/// A verification which did not pass. The contained [Report] lists the checks performed, the last
+/// of which is the one that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationFailure {
+    pub report: Report,
+}
+
+impl VerificationFailure {
+    /// The [ExitCode] bits describing which kind of check failed
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from_bits_retain(self.report.exit_code)
+    }
+
+    /// The check which failed
+    pub fn failed_check(&self) -> Option<&Check> {
+        self.report.checks.last()
+    }
+}
+
+impl Display for VerificationFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.failed_check() {
+            Some(check) => write!(
+                f,
+                "Verification of {} failed at check {}: {}",
+                self.report.mode,
+                check.name,
+                check.error.as_deref().unwrap_or("unknown error")
+            ),
+            None => write!(f, "Verification of {} failed", self.report.mode),
+        }
+    }
+}
+
+impl std::error::Error for VerificationFailure {}
+
+/// Turn a finished [Report] into the result of the verification
+fn finish(report: Report) -> Result<Report, VerificationFailure> {
+    match report.exit_code {
+        0 => Ok(report),
+        _ => Err(VerificationFailure { report }),
+    }
+}
+
+/// Verify the well-formedness as well as the syntactical and cryptographical correctness of an
+/// encoded ID-Cert, and that it is valid at the time `at`, given in seconds since the UNIX epoch.
+/// The certificate is verified with the [SignatureAlgorithm] named in its algorithm identifiers and
+/// has to be signed by the issuer key of `keys`.
+pub fn verify_id_cert(
+    value: &[u8],
+    encoding: Format,
+    target: Target,
+    at: u64,
+    keys: &KeyRing,
+) -> Result<Report, VerificationFailure> {
+    let algorithm = match select_algorithm(value, encoding) {
+        Ok(algorithm) => algorithm,
+        Err(e) => {
+            return finish(Report::new("id-cert").fail(
+                "algorithm",
+                ExitCode::UNSUPPORTED_ALGORITHM.bits(),
+                e,
+            ))
+        }
+    };
+    finish(
+        with_algorithm!(
+            algorithm,
+            A => verify_certificate::<A>(value, encoding, target, at, keys)
+        )
+        .with_algorithm(algorithm),
+    )
+}
+
+/// Verify the well-formedness as well as the syntactical and cryptographical correctness of an
+/// encoded ID-CSR. The ID-CSR is verified with the [SignatureAlgorithm] named in its algorithm
+/// identifiers and has to be signed by the key of `target` in `keys`.
+pub fn verify_id_csr(
+    value: &[u8],
+    encoding: Format,
+    target: Target,
+    keys: &KeyRing,
+) -> Result<Report, VerificationFailure> {
+    let algorithm = match select_algorithm(value, encoding) {
+        Ok(algorithm) => algorithm,
+        Err(e) => {
+            return finish(Report::new("id-csr").fail(
+                "algorithm",
+                ExitCode::UNSUPPORTED_ALGORITHM.bits(),
+                e,
+            ))
+        }
+    };
+    finish(
+        with_algorithm!(algorithm, A => verify_csr::<A>(value, encoding, target, keys))
+            .with_algorithm(algorithm),
+    )
+}
+
+/// Verify the cryptographical correctness of a JSON encoded message, signed with the given
+/// [SignatureAlgorithm]. See [crate::polyproto::message::Message] for the expected format.
+pub fn verify_message(
+    value: &[u8],
+    algorithm: SignatureAlgorithm,
+) -> Result<Report, VerificationFailure> {
+    finish(
+        with_algorithm!(algorithm, A => verify_message_with::<A>(value)).with_algorithm(algorithm),
+    )
+}
+
+/// Verify a JSON encoded challenge string, signed by an actor, at the time `at`, given in seconds
+/// since the UNIX epoch. See [SignedChallenge] for the expected format. The signature has to verify
+/// under the public key of the actor's encoded ID-Cert, which is verified with the
+/// [SignatureAlgorithm] named in its algorithm identifiers.
+pub fn verify_challenge(
+    value: &[u8],
+    id_cert: &[u8],
+    encoding: Format,
+    at: u64,
+) -> Result<Report, VerificationFailure> {
+    let algorithm = match select_algorithm(id_cert, encoding) {
+        Ok(algorithm) => algorithm,
+        Err(e) => {
+            return finish(Report::new("challenge").fail(
+                "algorithm",
+                ExitCode::UNSUPPORTED_ALGORITHM.bits(),
+                e,
+            ))
+        }
+    };
+    finish(
+        with_algorithm!(
+            algorithm,
+            A => verify_challenge_with::<A>(value, id_cert, encoding, at)
+        )
+        .with_algorithm(algorithm),
+    )
+}
+
+/// Verify that an encoded ID-Cert reflects the encoded ID-CSR it was issued from: Both have to
+/// name the same subject and public key, the ID-Cert may only grant the capabilities requested by
+/// the ID-CSR or a subset of them, and it has to be signed by the issuer key of `keys`. Both values
+/// are expected in the same encoding and are verified with the [SignatureAlgorithm] named in the
+/// ID-Cert's algorithm identifiers.
+pub fn verify_issuance(
+    id_cert: &[u8],
+    csr: &[u8],
+    encoding: Format,
+    keys: &KeyRing,
+) -> Result<Report, VerificationFailure> {
+    let algorithm = match select_algorithm(id_cert, encoding) {
+        Ok(algorithm) => algorithm,
+        Err(e) => {
+            return finish(Report::new("issuance").fail(
+                "algorithm",
+                ExitCode::UNSUPPORTED_ALGORITHM.bits(),
+                e,
+            ))
+        }
+    };
+    // The public key of the ID-Cert has to be the requested one, so it cannot be of another
+    // algorithm than the one of the ID-CSR
+    match select_algorithm(csr, encoding) {
+        Ok(requested) if requested == algorithm => (),
+        Ok(requested) => {
+            return finish(Report::new("issuance").with_algorithm(algorithm).fail(
+                "algorithm",
+                ExitCode::CONSTRAINT_VIOLATION.bits(),
+                format!(
+                    "The ID-CSR requests a certificate for {}, but the ID-Cert uses {}",
+                    requested, algorithm
+                ),
+            ))
+        }
+        Err(e) => {
+            return finish(Report::new("issuance").fail(
+                "algorithm",
+                ExitCode::UNSUPPORTED_ALGORITHM.bits(),
+                e,
+            ))
+        }
+    }
+    finish(
+        with_algorithm!(
+            algorithm,
+            A => verify_issuance_with::<A>(id_cert, csr, encoding, keys)
+        )
+        .with_algorithm(algorithm),
+    )
+}
+
+/// Verify a federation ID, e.g. `flori@polyphony.chat`, for its well-formedness. If the value is
+/// the RDN sequence of a subject instead, the federation ID is read from its UID, and its domain
+/// has to be the one named by the DC components of the subject, or one of its parent domains.
+pub fn verify_federation_id(value: &[u8]) -> Result<Report, VerificationFailure> {
+    let mut report = Report::new("federation-id");
+    let value = match std::str::from_utf8(value) {
+        Ok(value) => value.trim(),
+        Err(e) => return finish(report.fail("decode", ExitCode::GARBLED_INPUT.bits(), e)),
+    };
+    report.pass("decode");
+    // Federation IDs cannot contain an equals sign, RDN sequences always do
+    let federation_id = if value.contains('=') {
+        let subject = match RdnSequence::from_str(value) {
+            Ok(subject) => subject,
+            Err(e) => return finish(report.fail("parse", ExitCode::INVALID_INPUT.bits(), e)),
+        };
+        report.pass("parse");
+        report.subject = Some(subject.to_string());
+        match FederationId::from_subject(&subject) {
+            Ok(Some(federation_id)) => Ok(federation_id),
+            Ok(None) => {
+                return finish(report.fail(
+                    "federation_id",
+                    ExitCode::INVALID_INPUT.bits(),
+                    format!("The subject {} has no UID", subject),
+                ))
+            }
+            Err(error) => Err(error),
+        }
+    } else {
+        FederationId::from_str(value)
+    };
+    match federation_id {
+        Ok(_) => report.pass("federation_id"),
+        Err(error) => {
+            let code = federation_id_error_to_exit_code(&error);
+            return finish(report.fail("federation_id", code, error));
+        }
+    }
+    finish(report)
+}
+
+/// Decode the capabilities an encoded ID-Cert grants from its key usage and basic constraints
+/// extensions and record them in the report. Every capability in `require` has to be granted, none
+/// of the ones in `forbid` may be. The ID-Cert is only parsed, use [verify_id_cert] to verify it.
+pub fn verify_capabilities(
+    value: &[u8],
+    encoding: Format,
+    require: &[Capability],
+    forbid: &[Capability],
+) -> Result<Report, VerificationFailure> {
+    finish(inspect_capabilities(value, encoding, require, forbid))
+}
+
+/// Verify a chain of encoded ID-Certs, ordered from the end-entity certificate up to the
+/// certificate closest to the trust anchor. The trust anchor is the key stored in the file
+/// `trust_anchor`, or the issuer key of `keys`, if no file is given. All certificates have to use
+/// the same [SignatureAlgorithm].
+pub fn verify_chain(
+    values: &[Vec<u8>],
+    encoding: Format,
+    trust_anchor: Option<&Path>,
+    keys: &KeyRing,
+) -> Result<Report, VerificationFailure> {
+    let mut chain_algorithm = None;
+    for (index, value) in values.iter().enumerate() {
+        let check = format!("certificate[{}].algorithm", index);
+        let algorithm = match select_algorithm(value, encoding) {
+            Ok(algorithm) => algorithm,
+            Err(e) => {
+                return finish(Report::new("chain").fail(
+                    &check,
+                    ExitCode::UNSUPPORTED_ALGORITHM.bits(),
+                    e,
+                ))
+            }
+        };
+        // Every certificate is signed with the key of the next one, so all of them have to use the
+        // same algorithm
+        match chain_algorithm {
+            Some(chain_algorithm) if chain_algorithm != algorithm => {
+                return finish(Report::new("chain").fail(
+                    &check,
+                    ExitCode::UNSUPPORTED_ALGORITHM.bits(),
+                    format!(
+                        "Chains mixing signature algorithms are not supported, found {} and {}",
+                        chain_algorithm, algorithm
+                    ),
+                ))
+            }
+            _ => chain_algorithm = Some(algorithm),
+        }
+    }
+    let algorithm = chain_algorithm.unwrap_or(DEFAULT_ALGORITHM);
+    finish(
+        with_algorithm!(
+            algorithm,
+            A => verify_chain_with_anchor::<A>(values, encoding, trust_anchor, keys)
+        )
+        .with_algorithm(algorithm),
+    )
+}
+
+/// The algorithm values whose algorithm identifiers cannot be read are verified with. Since these
+/// values cannot be parsed, the algorithm only influences the reason given for the failure.
+const DEFAULT_ALGORITHM: SignatureAlgorithm = SignatureAlgorithm::Ed25519;
+
+/// Determine the [SignatureAlgorithm] an encoded ID-Cert or ID-CSR has to be verified with. Values
+/// whose algorithm identifiers cannot be read are verified with the [DEFAULT_ALGORITHM], so that
+/// the verification reports why they cannot be read. Returns an error describing the algorithm if
+/// stimmgabel does not implement it.
+fn select_algorithm(value: &[u8], encoding: Format) -> Result<SignatureAlgorithm, String> {
+    let Ok(der) = decode_der(value, encoding) else {
+        return Ok(DEFAULT_ALGORITHM);
+    };
+    match detect_algorithm(&der) {
+        DetectedAlgorithm::Supported(algorithm) => Ok(algorithm),
+        DetectedAlgorithm::Unsupported(e) => Err(e),
+        DetectedAlgorithm::Unreadable => Ok(DEFAULT_ALGORITHM),
+    }
+}
+
+/// Load the trust anchor, which is the issuer key unless a key file is given, and verify the chain
+/// of certificates with it
+fn verify_chain_with_anchor<A: Algorithm>(
+    values: &[Vec<u8>],
+    encoding: Format,
+    trust_anchor: Option<&Path>,
+    keys: &KeyRing,
+) -> Report {
+    let trust_anchor = match trust_anchor {
+        Some(path) => KeyFile::load(path).and_then(|key| key.verifying_key::<A>()),
+        None => keys.verifying_key::<A>(Target::Homeserver),
+    };
+    match trust_anchor {
+        Ok(trust_anchor) => verify_chain_with::<A>(values, encoding, &trust_anchor),
+        Err(e) => Report::new("chain").fail("trust_anchor", ExitCode::INVALID_INPUT.bits(), e),
+    }
+}
+
+/// Verify the well-formedness as well as the syntactical and cryptographical correctness of a given
+/// certificate value, and that it is valid at the time `at`, given in seconds since the UNIX epoch.
+/// This function returns a [Report] of the checks performed.
+fn verify_certificate<A: Algorithm>(
+    value: &[u8],
+    encoding: Format,
+    target: Target,
+    at: u64,
+    keys: &KeyRing,
+) -> Report {
+    let mut report = Report::new("id-cert");
+    let der = match decode_der(value, encoding) {
+        Ok(der) => der,
+        Err(e) => return report.fail("decode", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("decode");
+    let certificate = match check_signature::<A>(&der)
+        .and_then(|_| IdCert::<A::Signature, A::PublicKey>::from_der(&der))
+    {
+        Ok(certificate) => certificate,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("parse", code, error);
+        }
+    };
+    report.pass("parse");
+    report.subject = Some(certificate.id_cert_tbs.subject.to_string());
+    report.issuer = Some(certificate.id_cert_tbs.issuer.to_string());
+    let issuer_key = match keys.verifying_key::<A>(Target::Homeserver) {
+        Ok(key) => key,
+        Err(e) => return report.fail("signature", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    match verify_certificate_signature::<A>(&certificate, &issuer_key) {
+        Ok(_) => report.pass("signature"),
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("signature", code, error);
+        }
+    }
+    let validity = &certificate.id_cert_tbs.validity;
+    if at < validity.not_before.to_unix_duration().as_secs() {
+        return report.fail(
+            "validity",
+            ExitCode::NOT_YET_VALID.bits(),
+            format!(
+                "The certificate is not valid before {}",
+                validity.not_before
+            ),
+        );
+    }
+    if at > validity.not_after.to_unix_duration().as_secs() {
+        return report.fail(
+            "validity",
+            ExitCode::EXPIRED.bits(),
+            format!("The certificate expired at {}", validity.not_after),
+        );
+    }
+    report.pass("validity");
+    let (check, validation_result) = match target {
+        Target::Actor => ("validate_actor", certificate.validate_actor()),
+        Target::Homeserver => ("validate_home_server", certificate.validate_home_server()),
+    };
+    match validation_result {
+        Ok(_) => report.pass(check),
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail(check, code, error);
+        }
+    }
+    check_subject(report, &certificate.id_cert_tbs.subject, target)
+}
+
+/// Verify that `certificate` was signed by the private key belonging to `issuer_key`. The signature
+/// is checked over the DER encoded TBS part of the certificate.
+fn verify_certificate_signature<A: Algorithm>(
+    certificate: &IdCert<A::Signature, A::PublicKey>,
+    issuer_key: &A::PublicKey,
+) -> Result<(), ConversionError> {
+    let signature_data = certificate.signature_data()?;
+    issuer_key
+        .verify_signature(&certificate.signature, &signature_data)
+        .map_err(ConversionError::IdCertError)
+}
+
+/// Verify a chain of certificates, ordered from the end-entity certificate up to the certificate
+/// closest to the trust anchor. Every certificate has to be signed by the next one in the chain,
+/// name the next certificate's subject as its issuer and may only be issued by certificates which
+/// are allowed to act as a CA. The last certificate has to be signed by `trust_anchor`. This
+/// function returns a [Report] of the checks performed, in which the first broken link fails.
+fn verify_chain_with<A: Algorithm>(
+    values: &[Vec<u8>],
+    encoding: Format,
+    trust_anchor: &A::PublicKey,
+) -> Report {
+    let mut report = Report::new("chain");
+    if values.is_empty() {
+        return report.fail(
+            "parse",
+            ExitCode::INVALID_INPUT.bits(),
+            "The chain does not contain any certificates",
+        );
+    }
+    let mut certificates = Vec::with_capacity(values.len());
+    for (index, value) in values.iter().enumerate() {
+        let der = match decode_der(value, encoding) {
+            Ok(der) => der,
+            Err(e) => {
+                return report.fail(
+                    &format!("certificate[{}].decode", index),
+                    ExitCode::GARBLED_INPUT.bits(),
+                    e,
+                )
+            }
+        };
+        match check_signature::<A>(&der)
+            .and_then(|_| IdCert::<A::Signature, A::PublicKey>::from_der(&der))
+        {
+            Ok(certificate) => certificates.push(certificate),
+            Err(error) => {
+                let code = conversion_error_to_exit_code(&error);
+                return report.fail(&format!("certificate[{}].parse", index), code, error);
+            }
+        }
+    }
+    report.pass("parse");
+    let leaf = &certificates[0];
+    report.subject = Some(leaf.id_cert_tbs.subject.to_string());
+    report.issuer = Some(leaf.id_cert_tbs.issuer.to_string());
+
+    for (index, certificate) in certificates.iter().enumerate() {
+        let issuer = certificates.get(index + 1);
+        let issuer_key = match issuer {
+            Some(issuer) => &issuer.id_cert_tbs.subject_public_key,
+            None => trust_anchor,
+        };
+        let check = format!("link[{}].signature", index);
+        match verify_certificate_signature::<A>(certificate, issuer_key) {
+            Ok(_) => report.pass(&check),
+            Err(error) => return report.fail(&check, ExitCode::BROKEN_CHAIN.bits(), error),
+        }
+        let Some(issuer) = issuer else {
+            break;
+        };
+        let check = format!("link[{}].issuer", index);
+        if certificate.id_cert_tbs.issuer != issuer.id_cert_tbs.subject {
+            return report.fail(
+                &check,
+                ExitCode::BROKEN_CHAIN.bits(),
+                format!(
+                    "Issuer \"{}\" does not match the subject \"{}\" of the next certificate",
+                    certificate.id_cert_tbs.issuer, issuer.id_cert_tbs.subject
+                ),
+            );
+        }
+        report.pass(&check);
+        let check = format!("link[{}].basic_constraints", index);
+        let basic_constraints = &issuer.id_cert_tbs.capabilities.basic_constraints;
+        if !basic_constraints.ca {
+            return report.fail(
+                &check,
+                ExitCode::BROKEN_CHAIN.bits(),
+                format!(
+                    "Certificate {} issued certificate {}, but is not allowed to act as a CA",
+                    index + 1,
+                    index
+                ),
+            );
+        }
+        // The path length constraint limits the number of CA certificates which may follow the
+        // issuer in the chain. All certificates between the issuer and the leaf are CAs.
+        if let Some(path_length) = basic_constraints.path_length {
+            if index as u64 > path_length {
+                return report.fail(
+                    &check,
+                    ExitCode::BROKEN_CHAIN.bits(),
+                    format!(
+                        "Certificate {} has a path length constraint of {}, but is followed by {} CA certificates",
+                        index + 1,
+                        path_length,
+                        index
+                    ),
+                );
+            }
+        }
+        report.pass(&check);
+    }
+    report
+}
+
+/// Verify the cryptographical correctness of a given message value. The signature is expected to
+/// have been made over the UTF-8 bytes of the `message` field, while `signature` and `public_key`
+/// are expected to be base64 encoded raw values of the [Algorithm] `A`. As post-quantum signatures
+/// and keys are several kilobytes large, their base64 encoding may be wrapped over multiple lines.
+/// This function returns a [Report] of the checks performed.
+fn verify_message_with<A: Algorithm>(value: &[u8]) -> Report {
+    let mut report = Report::new("message");
+    let message: Message = match serde_json::from_slice(value) {
+        Ok(message) => message,
+        Err(e) => return report.fail("parse", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    report.pass("parse");
+    let signature_bytes = match decode_base64(&message.signature) {
+        Ok(bytes) => bytes,
+        Err(e) => return report.fail("decode_signature", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    let signature = match A::signature_from_bytes(&signature_bytes) {
+        Ok(signature) => signature,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("decode_signature", code, error);
+        }
+    };
+    report.pass("decode_signature");
+    let public_key_bytes = match decode_base64(&message.public_key) {
+        Ok(bytes) => bytes,
+        Err(e) => return report.fail("decode_public_key", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    let public_key = match A::public_key_from_bytes(&public_key_bytes) {
+        Ok(key) => key,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("decode_public_key", code, error);
+        }
+    };
+    report.pass("decode_public_key");
+    match public_key.verify_signature(&signature, message.message.as_bytes()) {
+        Ok(_) => report.pass("signature"),
+        Err(error) => {
+            let code = public_key_error_to_exit_code(&error);
+            return report.fail("signature", code, error);
+        }
+    }
+    report
+}
+
+/// Verify a signed challenge string: It has to be well-formed, must not have expired at the time
+/// `at` and its signature has to verify under the public key of the encoded ID-Cert `id_cert`.
+fn verify_challenge_with<A: Algorithm>(
+    value: &[u8],
+    id_cert: &[u8],
+    encoding: Format,
+    at: u64,
+) -> Report {
+    let mut report = Report::new("challenge");
+    let challenge: SignedChallenge = match serde_json::from_slice(value) {
+        Ok(challenge) => challenge,
+        Err(e) => return report.fail("parse", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    report.pass("parse");
+    let length = challenge.challenge.chars().count();
+    if !CHALLENGE_LENGTH.contains(&length) {
+        return report.fail(
+            "length",
+            ExitCode::CONSTRAINT_VIOLATION.bits(),
+            format!(
+                "Challenge strings have to be {} to {} characters long, got {}",
+                CHALLENGE_LENGTH.start(),
+                CHALLENGE_LENGTH.end(),
+                length
+            ),
+        );
+    }
+    report.pass("length");
+    if at > challenge.expires {
+        return report.fail(
+            "expires",
+            ExitCode::EXPIRED.bits(),
+            format!(
+                "The challenge string expired at {}, which lies before {}",
+                challenge.expires, at
+            ),
+        );
+    }
+    report.pass("expires");
+    let der = match decode_der(id_cert, encoding) {
+        Ok(der) => der,
+        Err(e) => return report.fail("decode_id_cert", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("decode_id_cert");
+    let certificate = match check_signature::<A>(&der)
+        .and_then(|_| IdCert::<A::Signature, A::PublicKey>::from_der(&der))
+    {
+        Ok(certificate) => certificate,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("parse_id_cert", code, error);
+        }
+    };
+    report.pass("parse_id_cert");
+    report.subject = Some(certificate.id_cert_tbs.subject.to_string());
+    report.issuer = Some(certificate.id_cert_tbs.issuer.to_string());
+    let signature_bytes = match decode_base64(&challenge.signature) {
+        Ok(bytes) => bytes,
+        Err(e) => return report.fail("decode_signature", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    let signature = match A::signature_from_bytes(&signature_bytes) {
+        Ok(signature) => signature,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("decode_signature", code, error);
+        }
+    };
+    report.pass("decode_signature");
+    match certificate
+        .id_cert_tbs
+        .subject_public_key
+        .verify_signature(&signature, challenge.challenge.as_bytes())
+    {
+        Ok(_) => report.pass("signature"),
+        Err(error) => {
+            let code = public_key_error_to_exit_code(&error);
+            return report.fail("signature", code, error);
+        }
+    }
+    report
+}
+
+/// Verify that the encoded ID-Cert `id_cert` was issued from the encoded ID-CSR `csr` by the holder
+/// of the issuer key. This function returns a [Report] of the checks performed.
+fn verify_issuance_with<A: Algorithm>(
+    id_cert: &[u8],
+    csr: &[u8],
+    encoding: Format,
+    keys: &KeyRing,
+) -> Report {
+    let mut report = Report::new("issuance");
+    let csr_der = match decode_der(csr, encoding) {
+        Ok(der) => der,
+        Err(e) => return report.fail("decode_id_csr", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("decode_id_csr");
+    let csr = match check_signature::<A>(&csr_der)
+        .and_then(|_| IdCsr::<A::Signature, A::PublicKey>::from_der(&csr_der))
+    {
+        Ok(csr) => csr,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("parse_id_csr", code, error);
+        }
+    };
+    report.pass("parse_id_csr");
+    let id_cert_der = match decode_der(id_cert, encoding) {
+        Ok(der) => der,
+        Err(e) => return report.fail("decode_id_cert", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("decode_id_cert");
+    let certificate = match check_signature::<A>(&id_cert_der)
+        .and_then(|_| IdCert::<A::Signature, A::PublicKey>::from_der(&id_cert_der))
+    {
+        Ok(certificate) => certificate,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("parse_id_cert", code, error);
+        }
+    };
+    report.pass("parse_id_cert");
+    report.subject = Some(certificate.id_cert_tbs.subject.to_string());
+    report.issuer = Some(certificate.id_cert_tbs.issuer.to_string());
+    if certificate.id_cert_tbs.subject != csr.inner_csr.subject {
+        return report.fail(
+            "subject",
+            ExitCode::CONSTRAINT_VIOLATION.bits(),
+            format!(
+                "The ID-CSR requests a certificate for {}, but the ID-Cert was issued to {}",
+                csr.inner_csr.subject, certificate.id_cert_tbs.subject
+            ),
+        );
+    }
+    report.pass("subject");
+    // Both values were parsed by polyproto already, so x509-cert can parse them as well
+    let requested_key =
+        x509_cert::request::CertReq::from_der(&csr_der).map(|request| request.info.public_key);
+    let certified_key = x509_cert::Certificate::from_der(&id_cert_der)
+        .map(|certificate| certificate.tbs_certificate.subject_public_key_info);
+    match (requested_key, certified_key) {
+        (Ok(requested_key), Ok(certified_key)) if requested_key == certified_key => {
+            report.pass("public_key")
+        }
+        (Ok(_), Ok(_)) => {
+            return report.fail(
+                "public_key",
+                ExitCode::CONSTRAINT_VIOLATION.bits(),
+                "The ID-Cert certifies another public key than the one of the ID-CSR",
+            )
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            return report.fail("public_key", ExitCode::GARBLED_INPUT.bits(), e)
+        }
+    }
+    match granted_capabilities(
+        &csr.inner_csr.capabilities,
+        &certificate.id_cert_tbs.capabilities,
+    ) {
+        Ok(()) => report.pass("capabilities"),
+        Err(e) => return report.fail("capabilities", ExitCode::CONSTRAINT_VIOLATION.bits(), e),
+    }
+    let issuer_key = match keys.verifying_key::<A>(Target::Homeserver) {
+        Ok(key) => key,
+        Err(e) => return report.fail("signature", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    match verify_certificate_signature::<A>(&certificate, &issuer_key) {
+        Ok(_) => report.pass("signature"),
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("signature", code, error);
+        }
+    }
+    report
+}
+
+/// Check that the capabilities `granted` by an ID-Cert are the ones `requested` by its ID-CSR, or a
+/// subset of them: Every granted key usage has to be requested, and the ID-Cert may only act as a CA
+/// with at most the requested path length if the ID-CSR asks for it.
+fn granted_capabilities(requested: &Capabilities, granted: &Capabilities) -> Result<(), String> {
+    if let Some(key_usage) = granted
+        .key_usage
+        .key_usages
+        .iter()
+        .find(|key_usage| !requested.key_usage.key_usages.contains(key_usage))
+    {
+        return Err(format!(
+            "The ID-Cert grants the key usage {:?}, which the ID-CSR does not request",
+            key_usage
+        ));
+    }
+    let (requested, granted) = (&requested.basic_constraints, &granted.basic_constraints);
+    if !granted.ca {
+        return Ok(());
+    }
+    if !requested.ca {
+        return Err("The ID-Cert may act as a CA, which the ID-CSR does not request".to_string());
+    }
+    match (requested.path_length, granted.path_length) {
+        (Some(requested), Some(granted)) if granted > requested => Err(format!(
+            "The ID-Cert allows a path length of {}, but the ID-CSR requests at most {}",
+            granted, requested
+        )),
+        (Some(requested), None) => Err(format!(
+            "The ID-Cert allows any path length, but the ID-CSR requests at most {}",
+            requested
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Decode the capabilities granted by a given certificate value and check them against the
+/// `require`d and `forbid`den ones. The extensions are decoded on their own instead of through
+/// polyproto, so that capabilities polyproto would reject can be inspected as well. This function
+/// returns a [Report] of the checks performed.
+fn inspect_capabilities(
+    value: &[u8],
+    encoding: Format,
+    require: &[Capability],
+    forbid: &[Capability],
+) -> Report {
+    let mut report = Report::new("capabilities");
+    let der = match decode_der(value, encoding) {
+        Ok(der) => der,
+        Err(e) => return report.fail("decode", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("decode");
+    let certificate = match x509_cert::Certificate::from_der(&der) {
+        Ok(certificate) => certificate.tbs_certificate,
+        Err(e) => return report.fail("parse", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("parse");
+    report.subject = Some(certificate.subject.to_string());
+    report.issuer = Some(certificate.issuer.to_string());
+    let extensions = certificate.extensions.unwrap_or_default();
+    // A missing extension grants nothing
+    let key_usage = match decode_extension::<KeyUsage>(&extensions) {
+        Ok(key_usage) => key_usage.map(|key_usage| key_usage.0).unwrap_or_default(),
+        Err(e) => return report.fail("key_usage", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    report.pass("key_usage");
+    let basic_constraints = match decode_extension::<BasicConstraints>(&extensions) {
+        Ok(basic_constraints) => basic_constraints.unwrap_or(BasicConstraints {
+            ca: false,
+            path_len_constraint: None,
+        }),
+        Err(e) => return report.fail("basic_constraints", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    report.pass("basic_constraints");
+    let granted: Vec<Capability> = Capability::value_variants()
+        .iter()
+        .copied()
+        .filter(|capability| match key_usage_flag(*capability) {
+            Some(flag) => key_usage.contains(flag),
+            None => basic_constraints.ca,
+        })
+        .collect();
+    report.capabilities = Some(GrantedCapabilities {
+        key_usage: granted
+            .iter()
+            .filter(|capability| key_usage_flag(**capability).is_some())
+            .map(Capability::to_string)
+            .collect(),
+        ca: basic_constraints.ca,
+        path_length: basic_constraints.path_len_constraint,
+    });
+    let list = |capabilities: Vec<&Capability>| {
+        capabilities
+            .iter()
+            .map(|capability| capability.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if !require.is_empty() {
+        let missing: Vec<&Capability> = require
+            .iter()
+            .filter(|capability| !granted.contains(capability))
+            .collect();
+        if !missing.is_empty() {
+            return report.fail(
+                "require",
+                ExitCode::CONSTRAINT_VIOLATION.bits(),
+                format!("The ID-Cert does not grant {}", list(missing)),
+            );
+        }
+        report.pass("require");
+    }
+    if !forbid.is_empty() {
+        let present: Vec<&Capability> = forbid
+            .iter()
+            .filter(|capability| granted.contains(capability))
+            .collect();
+        if !present.is_empty() {
+            return report.fail(
+                "forbid",
+                ExitCode::CONSTRAINT_VIOLATION.bits(),
+                format!("The ID-Cert grants the forbidden {}", list(present)),
+            );
+        }
+        report.pass("forbid");
+    }
+    report
+}
+
+/// Decode the extension `T` from `extensions`, if it is present
+fn decode_extension<T: AssociatedOid + DecodeOwned>(
+    extensions: &[Extension],
+) -> Result<Option<T>, polyproto::der::Error> {
+    extensions
+        .iter()
+        .find(|extension| extension.extn_id == T::OID)
+        .map(|extension| T::from_der(extension.extn_value.as_bytes()))
+        .transpose()
+}
+
+/// The key usage bit standing for `capability`, or `None` for acting as a CA, which is granted by
+/// the basic constraints instead
+fn key_usage_flag(capability: Capability) -> Option<KeyUsages> {
+    match capability {
+        Capability::DigitalSignature => Some(KeyUsages::DigitalSignature),
+        // Recent editions of X.509 renamed non-repudiation to content commitment
+        Capability::ContentCommitment => Some(KeyUsages::NonRepudiation),
+        Capability::KeyEncipherment => Some(KeyUsages::KeyEncipherment),
+        Capability::DataEncipherment => Some(KeyUsages::DataEncipherment),
+        Capability::KeyAgreement => Some(KeyUsages::KeyAgreement),
+        Capability::KeyCertSign => Some(KeyUsages::KeyCertSign),
+        Capability::CrlSign => Some(KeyUsages::CRLSign),
+        Capability::EncipherOnly => Some(KeyUsages::EncipherOnly),
+        Capability::DecipherOnly => Some(KeyUsages::DecipherOnly),
+        Capability::Ca => None,
+    }
+}
+
+/// Verify the well-formedness as well as the syntactical and cryptographical correctness of a given
+/// CSR value. This function returns a [Report] of the checks performed.
+fn verify_csr<A: Algorithm>(
+    value: &[u8],
+    encoding: Format,
+    target: Target,
+    keys: &KeyRing,
+) -> Report {
+    let mut report = Report::new("id-csr");
+    let der = match decode_der(value, encoding) {
+        Ok(der) => der,
+        Err(e) => return report.fail("decode", ExitCode::GARBLED_INPUT.bits(), e),
+    };
+    report.pass("decode");
+    let csr = match check_signature::<A>(&der)
+        .and_then(|_| IdCsr::<A::Signature, A::PublicKey>::from_der(&der))
+    {
+        Ok(csr) => csr,
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("parse", code, error);
+        }
+    };
+    report.pass("parse");
+    report.subject = Some(csr.inner_csr.subject.to_string());
+    let public_key = match keys.verifying_key::<A>(target) {
+        Ok(key) => key,
+        Err(e) => return report.fail("signature", ExitCode::INVALID_INPUT.bits(), e),
+    };
+    let signature_result = csr.signature_data().and_then(|signature_data| {
+        public_key
+            .verify_signature(&csr.signature, &signature_data)
+            .map_err(ConversionError::IdCertError)
+    });
+    match signature_result {
+        Ok(_) => report.pass("signature"),
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail("signature", code, error);
+        }
+    }
+    let (check, validation_result) = match target {
+        Target::Actor => ("validate_actor", csr.validate_actor()),
+        Target::Homeserver => ("validate_home_server", csr.validate_home_server()),
+    };
+    match validation_result {
+        Ok(_) => report.pass(check),
+        Err(error) => {
+            let code = conversion_error_to_exit_code(&error);
+            return report.fail(check, code, error);
+        }
+    }
+    check_subject(report, &csr.inner_csr.subject, target)
+}
+
+/// Check the session ID in the uniqueIdentifier of `subject` against the rules for `target`, and
+/// that the federation ID in its UID is well-formed and belongs to the domain named by its DC
+/// components. Subjects without a UID are not checked for a federation ID.
+fn check_subject(mut report: Report, subject: &RdnSequence, target: Target) -> Report {
+    match session_id_of(subject, target) {
+        Ok(_) => report.pass("session_id"),
+        Err(error) => {
+            let check = format!("session_id.{}", error.rule());
+            return report.fail(&check, ExitCode::CONSTRAINT_VIOLATION.bits(), error);
+        }
+    }
+    match FederationId::from_subject(subject) {
+        Ok(Some(_)) => report.pass("federation_id"),
+        Ok(None) => (),
+        Err(error) => {
+            let code = federation_id_error_to_exit_code(&error);
+            return report.fail("federation_id", code, error);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use ed25519_dalek::Signer;
+    use polyproto::certs::capabilities::Capabilities;
+    use polyproto::certs::idcert::IdCert;
+    use polyproto::certs::idcsr::IdCsr;
+    use polyproto::der::asn1::Uint;
+    use polyproto::der::{Decode, Encode};
+    use polyproto::key::{PrivateKey, PublicKey};
+    use polyproto::RdnSequence;
+    use x509_cert::time::Validity;
+
+    use crate::algorithm::{Algorithm, EcdsaP256, EcdsaP384, Ed25519, Ed448, MlDsa44, MlDsa65};
+    use crate::errors::ExitCode;
+    use crate::keyring::KeyRing;
+    use crate::options::{Capability, Format, SignatureAlgorithm, Target};
+    use crate::polyproto::challenge::SignedChallenge;
+    use crate::polyproto::keys::{PrivateKeyEd25519, PublicKeyEd25519};
+    use crate::polyproto::message::Message;
+    use crate::polyproto::signature::SignatureEd25519;
+
+    use super::{
+        detect_algorithm, granted_capabilities, verify_capabilities, verify_certificate,
+        verify_chain_with, verify_challenge, verify_csr, verify_federation_id, verify_id_cert,
+        verify_issuance, verify_message, verify_message_with, DetectedAlgorithm,
+    };
+
+    fn signed_message(message: &str) -> Message {
+        let signature = crate::builtin_keys::ED25519_PRIVATE_ACTOR_KEY.sign(message.as_bytes());
+        Message {
+            message: message.to_string(),
+            signature: BASE64.encode(signature.to_bytes()),
+            public_key: BASE64.encode(crate::builtin_keys::ED25519_PUBLIC_ACTOR_KEY.as_bytes()),
+        }
+    }
+
+    fn actor_csr<A: Algorithm>(subject: &str) -> IdCsr<A::Signature, A::PublicKey> {
+        actor_csr_signed_by::<A>(subject, &A::builtin_private_key(Target::Actor))
+    }
+
+    fn actor_csr_signed_by<A: Algorithm>(
+        subject: &str,
+        private_key: &A::PrivateKey,
+    ) -> IdCsr<A::Signature, A::PublicKey> {
+        IdCsr::new(
+            &RdnSequence::from_str(subject).unwrap(),
+            private_key,
+            &Capabilities::default_actor(),
+        )
+        .unwrap()
+    }
+
+    fn issued_actor_certificate<A: Algorithm>() -> IdCert<A::Signature, A::PublicKey> {
+        IdCert::from_actor_csr(
+            actor_csr::<A>(
+                "CN=flori,DC=www,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+            ),
+            &A::builtin_private_key(Target::Homeserver),
+            Uint::new(&[1]).unwrap(),
+            RdnSequence::from_str("DC=www,DC=polyphony,DC=chat").unwrap(),
+            Validity::from_now(Duration::from_secs(60 * 60)).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn certificate_outside_validity_period_is_rejected() {
+        env_logger::try_init().unwrap_or(());
+        let certificate = issued_actor_certificate::<Ed25519>();
+        let not_before = certificate.id_cert_tbs.validity.not_before;
+        let not_after = certificate.id_cert_tbs.validity.not_after;
+        let der = certificate.to_der().unwrap();
+        let verify_at = |at: u64| {
+            verify_certificate::<Ed25519>(&der, Format::Der, Target::Actor, at, &KeyRing::default())
+                .exit_code
+        };
+        assert_eq!(
+            verify_at(not_before.to_unix_duration().as_secs() - 1),
+            ExitCode::NOT_YET_VALID.bits()
+        );
+        assert_eq!(
+            verify_at(not_after.to_unix_duration().as_secs() + 1),
+            ExitCode::EXPIRED.bits()
+        );
+    }
+
+    #[test]
+    fn verify_ecdsa_certificates_by_their_algorithm_identifier() {
+        env_logger::try_init().unwrap_or(());
+        let p256_certificate = issued_actor_certificate::<EcdsaP256>().to_der().unwrap();
+        let p384_certificate = issued_actor_certificate::<EcdsaP384>().to_der().unwrap();
+        assert_eq!(
+            detect_algorithm(&p256_certificate),
+            DetectedAlgorithm::Supported(SignatureAlgorithm::EcdsaP256)
+        );
+        assert_eq!(
+            detect_algorithm(&p384_certificate),
+            DetectedAlgorithm::Supported(SignatureAlgorithm::EcdsaP384)
+        );
+        let report = verify_certificate::<EcdsaP256>(
+            &p256_certificate,
+            Format::Der,
+            Target::Actor,
+            crate::encoding::unix_now(),
+            &KeyRing::default(),
+        );
+        assert_eq!(report.exit_code, 0);
+        let report = verify_certificate::<EcdsaP384>(
+            &p384_certificate,
+            Format::Der,
+            Target::Actor,
+            crate::encoding::unix_now(),
+            &KeyRing::default(),
+        );
+        assert_eq!(report.exit_code, 0);
+    }
+
+    #[test]
+    fn unknown_signature_algorithm_is_unsupported() {
+        env_logger::try_init().unwrap_or(());
+        let der = issued_actor_certificate::<Ed25519>().to_der().unwrap();
+        let mut certificate = x509_cert::Certificate::from_der(&der).unwrap();
+        // sha256WithRSAEncryption
+        certificate.signature_algorithm.oid =
+            polyproto::der::asn1::ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+        let failure = verify_id_cert(
+            &certificate.to_der().unwrap(),
+            Format::Der,
+            Target::Actor,
+            crate::encoding::unix_now(),
+            &KeyRing::default(),
+        )
+        .unwrap_err();
+        assert_eq!(failure.exit_code(), ExitCode::UNSUPPORTED_ALGORITHM)
+    }
+
+    #[test]
+    fn verify_chain_signed_by_trust_anchor() {
+        env_logger::try_init().unwrap_or(());
+        let certificate = issued_actor_certificate::<Ed25519>().to_der().unwrap();
+        let report = verify_chain_with::<Ed25519>(
+            &[certificate],
+            Format::Der,
+            &Ed25519::builtin_public_key(Target::Homeserver),
+        );
+        assert_eq!(report.exit_code, 0)
+    }
+
+    #[test]
+    fn chain_with_wrong_trust_anchor_is_broken() {
+        env_logger::try_init().unwrap_or(());
+        let certificate = issued_actor_certificate::<Ed25519>().to_der().unwrap();
+        let report = verify_chain_with::<Ed25519>(
+            &[certificate],
+            Format::Der,
+            &Ed25519::builtin_public_key(Target::Actor),
+        );
+        assert_eq!(report.exit_code, ExitCode::BROKEN_CHAIN.bits())
+    }
+
+    #[test]
+    fn empty_chain_fails_to_parse_once() {
+        env_logger::try_init().unwrap_or(());
+        let report = verify_chain_with::<Ed25519>(
+            &[],
+            Format::Der,
+            &Ed25519::builtin_public_key(Target::Homeserver),
+        );
+        assert_eq!(report.exit_code, ExitCode::INVALID_INPUT.bits());
+        assert_eq!(report.checks.len(), 1);
+        assert!(!report.checks[0].passed);
+        assert_eq!(report.checks[0].name, "parse");
+    }
+
+    #[test]
+    fn verify_signed_message() {
+        env_logger::try_init().unwrap_or(());
+        let message = signed_message("Hello, polyproto!");
+        let report =
+            verify_message_with::<Ed25519>(serde_json::to_string(&message).unwrap().as_bytes());
+        assert_eq!(report.exit_code, 0)
+    }
+
+    #[test]
+    fn failed_verification_is_an_error() {
+        env_logger::try_init().unwrap_or(());
+        let mut message = signed_message("Hello, polyproto!");
+        let encoded = serde_json::to_string(&message).unwrap();
+        assert!(verify_message(encoded.as_bytes(), SignatureAlgorithm::Ed25519).is_ok());
+        message.message = "Goodbye, polyproto!".to_string();
+        let encoded = serde_json::to_string(&message).unwrap();
+        let failure = verify_message(encoded.as_bytes(), SignatureAlgorithm::Ed25519).unwrap_err();
+        assert_eq!(failure.exit_code(), ExitCode::BAD_SIGNATURE);
+        assert_eq!(failure.failed_check().unwrap().name, "signature");
+    }
+
+    #[test]
+    fn tampered_message_fails_verification() {
+        env_logger::try_init().unwrap_or(());
+        let mut message = signed_message("Hello, polyproto!");
+        message.message = "Goodbye, polyproto!".to_string();
+        let report =
+            verify_message_with::<Ed25519>(serde_json::to_string(&message).unwrap().as_bytes());
+        assert_eq!(report.exit_code, ExitCode::BAD_SIGNATURE.bits())
+    }
+
+    #[test]
+    fn short_message_signature_is_invalid_input() {
+        env_logger::try_init().unwrap_or(());
+        let mut message = signed_message("Hello, polyproto!");
+        message.signature = BASE64.encode([0u8; 12]);
+        let report =
+            verify_message_with::<Ed25519>(serde_json::to_string(&message).unwrap().as_bytes());
+        assert_eq!(report.exit_code, ExitCode::INVALID_INPUT.bits())
+    }
+
+    #[test]
+    fn verify_ed448_signed_message() {
+        env_logger::try_init().unwrap_or(());
+        let private_key = Ed448::builtin_private_key(Target::Actor);
+        let signature = private_key.sign("Hello, polyproto!".as_bytes());
+        let message = Message {
+            message: "Hello, polyproto!".to_string(),
+            signature: BASE64.encode(signature.signature.to_bytes()),
+            public_key: BASE64.encode(private_key.pubkey().key.to_bytes()),
+        };
+        let encoded = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            verify_message_with::<Ed448>(encoded.as_bytes()).exit_code,
+            0
+        );
+        // An Ed448 signature is not accepted when Ed25519 is expected
+        assert_ne!(
+            verify_message_with::<Ed25519>(encoded.as_bytes()).exit_code,
+            0
+        );
+    }
+
+    #[test]
+    fn verify_ml_dsa_signed_message_with_wrapped_base64() {
+        env_logger::try_init().unwrap_or(());
+        let private_key = MlDsa44::builtin_private_key(Target::Actor);
+        let signature = private_key.sign("Hello, polyproto!".as_bytes());
+        let public_key = private_key.pubkey().public_key_info();
+        // Wrap the base64 encoded values the way PEM does
+        let wrap = |bytes: &[u8]| {
+            BASE64
+                .encode(bytes)
+                .as_bytes()
+                .chunks(64)
+                .map(|line| String::from_utf8_lossy(line).into_owned())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let message = Message {
+            message: "Hello, polyproto!".to_string(),
+            signature: wrap(&signature.signature),
+            public_key: wrap(public_key.public_key_bitstring.raw_bytes()),
+        };
+        let report =
+            verify_message_with::<MlDsa44>(serde_json::to_string(&message).unwrap().as_bytes());
+        assert_eq!(report.exit_code, 0)
+    }
+
+    #[test]
+    fn verify_ml_dsa_certificate() {
+        env_logger::try_init().unwrap_or(());
+        let certificate = issued_actor_certificate::<MlDsa65>().to_der().unwrap();
+        assert_eq!(
+            detect_algorithm(&certificate),
+            DetectedAlgorithm::Supported(SignatureAlgorithm::MlDsa65)
+        );
+        let report = verify_certificate::<MlDsa65>(
+            &certificate,
+            Format::Der,
+            Target::Actor,
+            crate::encoding::unix_now(),
+            &KeyRing::default(),
+        );
+        assert_eq!(report.exit_code, 0)
+    }
+
+    #[test]
+    fn truncated_ed25519_certificate_signature_is_invalid_input() {
+        env_logger::try_init().unwrap_or(());
+        let der = issued_actor_certificate::<Ed25519>().to_der().unwrap();
+        let mut certificate = x509_cert::Certificate::from_der(&der).unwrap();
+        certificate.signature =
+            polyproto::der::asn1::BitString::from_bytes(&certificate.signature.raw_bytes()[..32])
+                .unwrap();
+        let report = verify_certificate::<Ed25519>(
+            &certificate.to_der().unwrap(),
+            Format::Der,
+            Target::Actor,
+            crate::encoding::unix_now(),
+            &KeyRing::default(),
+        );
+        assert_eq!(report.exit_code, ExitCode::INVALID_INPUT.bits())
+    }
+
+    #[test]
+    fn verify_home_server_signed_actor_csr() {
+        env_logger::try_init().unwrap_or(());
+        let actor_cert_csr = actor_csr::<Ed25519>(
+            "CN=flori,DC=www,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+        );
+        let report = verify_csr::<Ed25519>(
+            actor_cert_csr
+                .to_pem(polyproto::der::pem::LineEnding::LF)
+                .unwrap()
+                .as_bytes(),
+            Format::Pem,
+            Target::Actor,
+            &KeyRing::default(),
+        );
+        assert_eq!(report.exit_code, 0)
+    }
+
+    #[test]
+    fn verify_base64_der_actor_csr() {
+        env_logger::try_init().unwrap_or(());
+        let actor_cert_csr = actor_csr::<Ed25519>(
+            "CN=flori,DC=www,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+        );
+        let encoded = BASE64.encode(actor_cert_csr.to_der().unwrap());
+        let report = verify_csr::<Ed25519>(
+            encoded.as_bytes(),
+            Format::DerBase64,
+            Target::Actor,
+            &KeyRing::default(),
+        );
+        assert_eq!(report.exit_code, 0)
+    }
+
+    #[test]
+    fn other_key_cannot_pass_verification_csr() {
+        env_logger::try_init().unwrap_or(());
+        let mut csprng = rand::rngs::OsRng;
+        // Generate a key pair
+        let private_key = PrivateKeyEd25519::gen_keypair(&mut csprng);
+        let actor_cert_csr = actor_csr_signed_by::<Ed25519>(
+            "CN=flori,DC=www,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+            &private_key,
+        );
+        let report = verify_csr::<Ed25519>(
+            actor_cert_csr
+                .to_pem(polyproto::der::pem::LineEnding::LF)
+                .unwrap()
+                .as_bytes(),
+            Format::Pem,
+            Target::Actor,
+            &KeyRing::default(),
+        );
+        assert_ne!(report.exit_code, 0)
+    }
+
+    fn signed_challenge(challenge: &str, expires: u64) -> SignedChallenge {
+        let signature = crate::builtin_keys::ED25519_PRIVATE_ACTOR_KEY.sign(challenge.as_bytes());
+        SignedChallenge {
+            challenge: challenge.to_string(),
+            expires,
+            signature: BASE64.encode(signature.to_bytes()),
+        }
+    }
+
+    #[test]
+    fn challenge_signed_by_certified_key_passes_verification() {
+        env_logger::try_init().unwrap_or(());
+        let id_cert = issued_actor_certificate::<Ed25519>().to_der().unwrap();
+        let now = crate::encoding::unix_now();
+        let challenge =
+            signed_challenge("stimmgabel-challenge-0123456789abcdef0123456789", now + 60);
+        let verify = |challenge: &SignedChallenge, at: u64| {
+            verify_challenge(
+                serde_json::to_string(challenge).unwrap().as_bytes(),
+                &id_cert,
+                Format::Der,
+                at,
+            )
+        };
+        let report = verify(&challenge, now).unwrap();
+        assert_eq!(report.algorithm.as_deref(), Some("ed25519"));
+        let expired = verify(&challenge, now + 61).unwrap_err();
+        assert_eq!(expired.exit_code(), ExitCode::EXPIRED);
+        let tampered = SignedChallenge {
+            challenge: "stimmgabel-challenge-9876543210fedcba9876543210".to_string(),
+            ..challenge
+        };
+        let tampered = verify(&tampered, now).unwrap_err();
+        assert_eq!(tampered.exit_code(), ExitCode::BAD_SIGNATURE);
+        let short = verify(&signed_challenge("too short", now + 60), now).unwrap_err();
+        assert_eq!(short.exit_code(), ExitCode::CONSTRAINT_VIOLATION);
+    }
+
+    #[test]
+    fn id_cert_issued_from_csr_passes_verification() {
+        env_logger::try_init().unwrap_or(());
+        let subject =
+            "CN=flori,DC=polyphony,DC=chat,UID=flori@polyphony.chat,uniqueIdentifier=client1";
+        let other = actor_csr::<Ed25519>(
+            "CN=other,DC=polyphony,DC=chat,UID=other@polyphony.chat,uniqueIdentifier=client1",
+        );
+        let id_cert = IdCert::from_actor_csr(
+            actor_csr::<Ed25519>(subject),
+            &Ed25519::builtin_private_key(Target::Homeserver),
+            Uint::new(&[1]).unwrap(),
+            RdnSequence::from_str("DC=polyphony,DC=chat").unwrap(),
+            Validity::from_now(Duration::from_secs(60 * 60)).unwrap(),
+        )
+        .unwrap()
+        .to_der()
+        .unwrap();
+        let verify = |csr: &IdCsr<SignatureEd25519, PublicKeyEd25519>| {
+            verify_issuance(
+                &id_cert,
+                &csr.to_der().unwrap(),
+                Format::Der,
+                &KeyRing::default(),
+            )
+        };
+        let report = verify(&actor_csr::<Ed25519>(subject)).unwrap();
+        assert!(report
+            .checks
+            .iter()
+            .any(|check| check.name == "capabilities"));
+        let other = verify(&other).unwrap_err();
+        assert_eq!(other.failed_check().unwrap().name, "subject");
+        assert_eq!(other.exit_code(), ExitCode::CONSTRAINT_VIOLATION);
+    }
+
+    #[test]
+    fn id_cert_may_not_grant_more_than_requested() {
+        let actor = Capabilities::default_actor();
+        let home_server = Capabilities::default_home_server();
+        assert!(granted_capabilities(&actor, &actor).is_ok());
+        assert!(granted_capabilities(&home_server, &home_server).is_ok());
+        assert!(granted_capabilities(&actor, &home_server).is_err());
+    }
+
+    #[test]
+    fn uid_outside_of_domain_components_is_a_federation_id_mismatch() {
+        env_logger::try_init().unwrap_or(());
+        let csr = actor_csr::<Ed25519>(
+            "CN=flori,DC=polyphony,DC=social,UID=flori@polyphony.chat,uniqueIdentifier=client1",
+        );
+        let report = verify_csr::<Ed25519>(
+            &csr.to_der().unwrap(),
+            Format::Der,
+            Target::Actor,
+            &KeyRing::default(),
+        );
+        assert_eq!(report.checks.last().unwrap().name, "federation_id");
+        assert_eq!(report.exit_code, ExitCode::FEDERATION_ID_MISMATCH.bits());
+    }
+
+    #[test]
+    fn verify_federation_ids_and_subjects() {
+        assert!(verify_federation_id(b"flori@polyphony.chat").is_ok());
+        assert!(verify_federation_id(b"DC=polyphony,DC=chat,UID=flori@polyphony.chat").is_ok());
+        assert!(
+            verify_federation_id(b"DC=www,DC=polyphony,DC=chat,UID=flori@polyphony.chat").is_ok()
+        );
+        let illegal = verify_federation_id(b"flori!@polyphony.chat").unwrap_err();
+        assert_eq!(illegal.exit_code(), ExitCode::CONSTRAINT_VIOLATION);
+        let mismatch =
+            verify_federation_id(b"DC=polyphony,DC=social,UID=flori@polyphony.chat").unwrap_err();
+        assert_eq!(mismatch.exit_code(), ExitCode::FEDERATION_ID_MISMATCH);
+        let no_uid = verify_federation_id(b"DC=polyphony,DC=chat").unwrap_err();
+        assert_eq!(no_uid.exit_code(), ExitCode::INVALID_INPUT);
+    }
+
+    #[test]
+    fn actor_capabilities_are_inspected() {
+        env_logger::try_init().unwrap_or(());
+        let id_cert = issued_actor_certificate::<Ed25519>().to_der().unwrap();
+        let inspect = |require: &[Capability], forbid: &[Capability]| {
+            verify_capabilities(&id_cert, Format::Der, require, forbid)
+        };
+        let report = inspect(
+            &[Capability::DigitalSignature],
+            &[Capability::KeyCertSign, Capability::Ca],
+        )
+        .unwrap();
+        let capabilities = report.capabilities.unwrap();
+        assert!(capabilities
+            .key_usage
+            .contains(&"digital-signature".to_string()));
+        assert!(!capabilities.ca);
+        let ca_required = inspect(&[Capability::Ca], &[]).unwrap_err();
+        assert_eq!(ca_required.failed_check().unwrap().name, "require");
+        assert_eq!(ca_required.exit_code(), ExitCode::CONSTRAINT_VIOLATION);
+        let signing_forbidden = inspect(&[], &[Capability::DigitalSignature]).unwrap_err();
+        assert_eq!(signing_forbidden.failed_check().unwrap().name, "forbid");
+        assert!(signing_forbidden.report.capabilities.is_some());
+    }
+}